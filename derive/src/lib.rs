@@ -0,0 +1,256 @@
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index, LitStr, Path, Token};
+use syn::punctuated::Punctuated;
+
+/// Implements `Component`, configured by an optional `#[component(...)]` attribute:
+///
+/// - `storage = "table"` sets where the component is stored.
+/// - `on_add = path` and `on_remove = path` set lifecycle hooks, each a `fn(&mut Self)`.
+/// - `require(A, B, ...)` spawns `A::default()`, `B::default()`... whenever this component is
+///   spawned without them.
+/// - `serialize` registers the component for serialization, it must implement
+///   `serde::Serialize` and `serde::Deserialize`.
+/// - `reflect` exposes the fields of the component, it must implement `Reflect`.
+/// - `clone` lets entities with the component be cloned, it must implement `Clone`.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match component(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Implements `Resource`.
+#[proc_macro_derive(Resource)]
+pub fn derive_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::rylans_ecs::Resource for #name #ty_generics #where_clause {}
+    }.into()
+}
+
+fn component(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let mut storage = None;
+    let mut on_add = None;
+    let mut on_remove = None;
+    let mut required: Vec<Path> = Vec::new();
+    let mut serialize = false;
+    let mut reflect = false;
+    let mut clone = false;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("component")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("storage") {
+                let value: LitStr = meta.value()?.parse()?;
+                storage = Some(match value.value().as_str() {
+                    "table" => quote! { ::rylans_ecs::StorageType::Table },
+                    _ => return Err(Error::new_spanned(value, "unknown storage type, expected \"table\"")),
+                });
+            } else if meta.path.is_ident("on_add") {
+                on_add = Some(meta.value()?.parse::<Path>()?);
+            } else if meta.path.is_ident("on_remove") {
+                on_remove = Some(meta.value()?.parse::<Path>()?);
+            } else if meta.path.is_ident("require") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                required.extend(Punctuated::<Path, Token![,]>::parse_terminated(&content)?);
+            } else if meta.path.is_ident("serialize") {
+                serialize = true;
+            } else if meta.path.is_ident("reflect") {
+                reflect = true;
+            } else if meta.path.is_ident("clone") {
+                clone = true;
+            } else {
+                return Err(meta.error("unknown component attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    let storage = storage.map(|storage| quote! {
+        const STORAGE: ::rylans_ecs::StorageType = #storage;
+    });
+
+    let hooks = if on_add.is_some() || on_remove.is_some() {
+        let on_add = on_add.map(|path| quote! {
+            hooks.on_add = Some(|ptr| #path(unsafe { &mut *ptr.cast::<Self>() }));
+        });
+        let on_remove = on_remove.map(|path| quote! {
+            hooks.on_remove = Some(|ptr| #path(unsafe { &mut *ptr.cast::<Self>() }));
+        });
+
+        Some(quote! {
+            fn hooks(hooks: &mut ::rylans_ecs::ComponentHooks) {
+                #on_add
+                #on_remove
+            }
+        })
+    } else {
+        None
+    };
+
+    let required = if !required.is_empty() {
+        Some(quote! {
+            fn required(required: &mut Vec<::rylans_ecs::RequiredComponent>) {
+                #(required.push(::rylans_ecs::RequiredComponent::of::<#required>());)*
+            }
+        })
+    } else {
+        None
+    };
+
+    let serializer = serialize.then(|| quote! {
+        fn serializer() -> Option<::rylans_ecs::ComponentSerializer> {
+            Some(::rylans_ecs::ComponentSerializer::of::<Self>())
+        }
+    });
+
+    let reflect = reflect.then(|| quote! {
+        fn reflect() -> Option<fn() -> ::rylans_ecs::TypeInfo> {
+            Some(<Self as ::rylans_ecs::Reflect>::type_info)
+        }
+    });
+
+    let cloner = clone.then(|| quote! {
+        fn cloner() -> Option<fn(&Self) -> Self> {
+            Some(<Self as ::std::clone::Clone>::clone)
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rylans_ecs::Component for #name #ty_generics #where_clause {
+            #storage
+            #hooks
+            #required
+            #serializer
+            #reflect
+            #cloner
+        }
+    })
+}
+
+/// Implements `Reflect` for a struct whose fields all implement `Reflect`.
+/// Fields of tuple structs are named by their position.
+#[proc_macro_derive(Reflect)]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match reflect(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn reflect(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new_spanned(&input.ident, "Reflect can only be derived for structs")),
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    // the name of every field as a string, and as it is written in offset_of!.
+    let (names, members): (Vec<_>, Vec<_>) = fields.iter().enumerate().map(|(i, field)| match &field.ident {
+        Some(ident) => (ident.to_string(), quote! { #ident }),
+        None => {
+            let index = Index::from(i);
+            (i.to_string(), quote! { #index })
+        },
+    }).unzip();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in types.iter() {
+        where_clause.predicates.push(syn::parse_quote! { #ty: ::rylans_ecs::Reflect });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rylans_ecs::Reflect for #name #ty_generics #where_clause {
+            fn type_info() -> ::rylans_ecs::TypeInfo {
+                // every offset is taken from the struct itself.
+                unsafe {
+                    ::rylans_ecs::TypeInfo::new::<Self>(vec![#(
+                        ::rylans_ecs::FieldInfo::new::<#types>(#names, ::std::mem::offset_of!(Self, #members))
+                    ),*])
+                }
+            }
+        }
+    })
+}
+
+/// Implements `Bundle` for a struct whose fields are all components or bundles.
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match bundle(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn bundle(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new_spanned(&input.ident, "Bundle can only be derived for structs")),
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    // Bind every field to a local so the struct can be moved apart.
+    let bindings: Vec<_> = (0..fields.len()).map(|i| format_ident!("field_{}", i)).collect();
+    let destructure = match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|field| &field.ident);
+            quote! { let #name { #(#idents: #bindings),* } = self; }
+        },
+        Fields::Unnamed(_) => {
+            let indices = (0..fields.len()).map(Index::from);
+            quote! { let #name { #(#indices: #bindings),* } = self; }
+        },
+        Fields::Unit => quote! {},
+    };
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in types.iter() {
+        where_clause.predicates.push(syn::parse_quote! { #ty: ::rylans_ecs::Bundle });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // sound as both visit every field, in declaration order, through its own `Bundle` impl.
+    Ok(quote! {
+        unsafe impl #impl_generics ::rylans_ecs::Bundle for #name #ty_generics #where_clause {
+            fn infos(infos: &mut Vec<::rylans_ecs::ComponentInfo>) {
+                #(<#types as ::rylans_ecs::Bundle>::infos(infos);)*
+            }
+
+            #[allow(unused_variables)]
+            fn take(self, func: &mut dyn FnMut(*mut u8)) {
+                #destructure
+                #(<#types as ::rylans_ecs::Bundle>::take(#bindings, func);)*
+            }
+        }
+    })
+}
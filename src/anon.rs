@@ -1,170 +1,443 @@
 
+use std::any::TypeId;
 use std::ptr::NonNull;
 use std::alloc::Layout;
 use std::ptr;
 use std::alloc::realloc;
 use std::alloc::alloc;
+use std::alloc::dealloc;
+use std::alloc::handle_alloc_error;
 use std::mem::needs_drop;
+use std::mem::ManuallyDrop;
+use std::marker::PhantomData;
 
 use super::archetypes::ComponentId;
 use super::handle::Component;
+use super::components::{Components, StorageType, ComponentHooks, RequiredComponent, clone_component};
+use super::reflect::{self, Reflect, TypeInfo};
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
+#[cfg(feature = "serialize")]
+use super::serialize::ComponentSerializer;
+
+/// Everything needed to store a component without knowing its type. 
+///
+/// Ids are handed out by the Ecs a component is added to, so the info of a
+/// typed component has no id until it is resolved against one, and is only
+/// known by its `type_id` until then.
+#[derive(Copy, Clone)]
+pub struct ComponentInfo {
+    pub(crate) id: ComponentId,
+    /// The Rust type of the component, or `None` if it was defined at runtime.
+    pub(crate) type_id: Option<TypeId>,
+    pub(crate) name: &'static str,
+    pub(crate) layout: Layout,
+    /// Drops the value behind the pointer in place.
+    pub(crate) drop: Option<unsafe fn(*mut u8)>,
+    /// Writes a copy of the value behind the first pointer to the second.
+    pub(crate) clone: Option<unsafe fn(*const u8, *mut u8)>,
+    pub(crate) storage: StorageType,
+    pub(crate) hooks: ComponentHooks,
+    pub(crate) required: fn(&mut Vec<RequiredComponent>),
+    pub(crate) reflect: Option<fn() -> TypeInfo>,
+    #[cfg(feature = "serialize")]
+    pub(crate) serializer: Option<ComponentSerializer>,
+}
+
+impl ComponentInfo {
+    pub fn of<C: Component>() -> Self {
+        unsafe fn drop_as<T>(ptr: *mut u8) {
+            ptr.cast::<T>().drop_in_place();
+        }
+
+        let mut hooks = ComponentHooks::default();
+        C::hooks(&mut hooks);
+
+        Self {
+            id: u16::MAX,
+            type_id: Some(TypeId::of::<C>()),
+            name: C::name(),
+            layout: Layout::new::<C>(),
+            drop: if needs_drop::<C>() { Some(drop_as::<C> as unsafe fn(*mut u8)) } else { None },
+            clone: C::cloner().map(|_| clone_component::<C> as unsafe fn(*const u8, *mut u8)),
+            storage: C::STORAGE,
+            hooks,
+            required: C::required,
+            reflect: C::reflect(),
+            #[cfg(feature = "serialize")]
+            serializer: C::serializer(),
+        }
+    }
+
+    /// Describes a component that has no Rust type, such as one defined by a script.
+    /// It has no hooks, required components or serializer, and no id until it is
+    /// added with `Ecs::add_dynamic_component`.
+    ///
+    /// # Safety
+    /// `layout` must be the layout of the values of the component, `drop` must drop
+    /// a value in place, and `clone` must write a valid copy of the value behind its 
+    /// first pointer to the second. Every value spawned or inserted as this component
+    /// must be valid for them, and safe to send to and share between threads
+    /// like a `Send + Sync` type, as systems on any thread can reach them.
+    pub unsafe fn dynamic(name: &'static str, layout: Layout, drop: Option<unsafe fn(*mut u8)>, clone: Option<unsafe fn(*const u8, *mut u8)>) -> Self {
+        Self {
+            id: u16::MAX,
+            type_id: None,
+            name,
+            layout,
+            drop,
+            clone,
+            storage: StorageType::Table,
+            hooks: ComponentHooks::default(),
+            required: |_| {},
+            reflect: None,
+            #[cfg(feature = "serialize")]
+            serializer: None,
+        }
+    }
+
+    /// The id of the component in the Ecs it was added to, or `u16::MAX` before then.
+    pub fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    /// The Rust type of the component, or `None` if it was defined at runtime.
+    pub fn type_id(&self) -> Option<TypeId> {
+        self.type_id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn storage(&self) -> StorageType {
+        self.storage
+    }
+
+    pub fn hooks(&self) -> &ComponentHooks {
+        &self.hooks
+    }
+
+    /// Whether values of the component can be cloned, see `#[component(clone)]`.
+    pub fn is_cloneable(&self) -> bool {
+        self.clone.is_some()
+    }
+
+    /// The fields of the component, if it is reflected, see `#[component(reflect)]`.
+    pub fn reflect(&self) -> Option<TypeInfo> {
+        self.reflect.map(|reflect| reflect())
+    }
+
+    #[cfg(feature = "serialize")]
+    pub fn serializer(&self) -> Option<&ComponentSerializer> {
+        self.serializer.as_ref()
+    }
+
+    /// Errors unless this describes the component `C`.
+    pub fn check<C: Component>(&self) -> Result<()> {
+        if self.type_id != Some(TypeId::of::<C>()) {
+            start_trace!(EcsError::ComponentMismatch(C::name()))
+        }
+
+        Ok(())
+    }
+
+    /// Whether both describe the same component. Typed components are compared
+    /// by type, so this holds whether or not their ids have been resolved yet.
+    pub fn is(&self, other: &ComponentInfo) -> bool {
+        match (self.type_id, other.type_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.id == other.id,
+        }
+    }
+}
 
 /// Anonymously-Typed Vector
+/// 
+/// Owns every value stored inside of it. Values are dropped when they are
+/// destroyed with `destroy_swap`, when the vector is cleared, or when the 
+/// vector itself is dropped. Values that are moved out with `read` are 
+/// the responsibility of the caller. 
 pub struct AnonVec {
     inner: NonNull<u8>,
-    layout: Layout,
+    info: ComponentInfo,
     capacity: usize,
     len: usize,
-    drop: Option<fn(*mut u8)>,
-    cmpid: ComponentId,
 }
 
 impl AnonVec {
     pub fn new(anon: Anon) -> Self {
+        let mut vec = Self::empty(anon.info);
+        unsafe { vec.push_raw(anon.as_ptr()) };
+        anon.forget();
+        vec
+    }
+
+    /// Creates an empty vector for the component described by `info`. 
+    /// No memory is allocated until the first value is pushed. 
+    pub fn empty(info: ComponentInfo) -> Self {
         Self {
-            inner: anon.inner,
-            layout: anon.layout,
-            capacity: 1,
-            len: 1,
-            drop: anon.drop,
-            cmpid: anon.cmpid,
+            inner: dangling(info.layout),
+            info,
+            // ZSTs never need to allocate, so they have "infinite" capacity.
+            capacity: if info.layout.size() == 0 { usize::MAX } else { 0 },
+            len: 0,
         }
     }
 
+    pub fn info(&self) -> &ComponentInfo {
+        &self.info
+    }
+
+    /// A pointer to the first value.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.inner.as_ptr()
+    }
+
     /// Append a value to the back of the vector. 
-    pub fn push(&mut self, val: Anon) {
+    pub fn push(&mut self, val: Anon) -> Result<()> {
+        if !val.info.is(&self.info) {
+            start_trace!(EcsError::ComponentMismatch(val.info.name))
+        }
+
+        unsafe { self.push_raw(val.as_ptr()) };
+
+        // The value now lives in the vector, so release the
+        // box without dropping what was inside of it. 
+        val.forget();
+
+        Ok(())
+    }
+
+    /// Moves the value behind `src` to the back of the vector. 
+    /// 
+    /// # Safety
+    /// `src` must point to a valid value of this vector's component, which 
+    /// the vector takes ownership of. The caller must not drop it afterwards.
+    pub unsafe fn push_raw(&mut self, src: *const u8) {
+        // Allocate space as needed.
+        self.grow_if_full();
+
+        let size = self.info.layout.size();
+
+        // Copy `size` bytes from `src` to `inner.ptr + size * len`.
+        ptr::copy_nonoverlapping(src, self.inner.as_ptr().add(size * self.len), size);
+
+        // increment the length
+        self.len += 1;
+    }
+
+    /// Moves every value out of `other` and onto the back of this vector. 
+    pub fn append(&mut self, other: &mut AnonVec) -> Result<()> {
+        if !other.info.is(&self.info) {
+            start_trace!(EcsError::ComponentMismatch(other.info.name))
+        }
+
+        self.reserve(other.len);
+
         unsafe {
-            if val.cmpid != self.cmpid {
-                panic!("cmpids did not match!")
-            }
+            let size = self.info.layout.size();
 
-            // Allocate space as needed.
-            self.grow_if_full();
+            ptr::copy_nonoverlapping(
+                other.inner.as_ptr(), 
+                self.inner.as_ptr().add(size * self.len), 
+                size * other.len
+            );
+        }
+
+        self.len += other.len;
 
-            let size = self.layout.size();
+        // the values belong to self now, so other must forget them.
+        other.len = 0;
 
-            // Copy `size` bytes from `val.ptr` to `inner.ptr + size * len`.
-            ptr::copy(val.as_ptr(), self.inner.as_ptr().add(size * self.len), size);
+        Ok(())
+    }
 
-            // increment the length
-            self.len += 1;
+    /// Makes sure there is space for at least `additional` more values. 
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len + additional;
+
+        if required > self.capacity {
+            unsafe { self.realloc(required.max(self.capacity * 2)) }
         }
     }
 
-    /// Returns a pointer to the value at Index.
-    pub fn index(&self, index: usize) -> Anon {
-        unsafe {
-            // panic if out of bounds
-            if index >= self.len {
-                panic!("Index ({0}) must be less than the len! (len: ({1})", index, self.len);
-            }
+    /// Moves the value at Index out of the vector by copying it into a new [`Anon`].
+    /// 
+    /// # Safety
+    /// The slot at Index still holds the same bytes afterwards, so the caller must 
+    /// remove it with `destroy_nodrop` before it can be read, dropped or moved again.
+    pub(crate) unsafe fn read(&self, index: usize) -> Result<Anon> {
+        if index >= self.len {
+            start_trace!(EcsError::IndexOutOfBounds { index, len: self.len })
+        }
 
-            let size = self.layout.size();
+        let size = self.info.layout.size();
+        let anon = Anon::alloc(self.info);
 
-            // the location of the value
-            let src = self.inner.as_ptr().add(size * index);
+        // the location of the value
+        let src = self.inner.as_ptr().add(size * index);
 
-            Anon {
-                inner: NonNull::new(src).unwrap(),
-                drop: self.drop,
-                cmpid: self.cmpid,
-                layout: self.layout
-            }
+        ptr::copy_nonoverlapping(src, anon.as_ptr(), size);
+
+        Ok(anon)
+    }
+
+    /// Clones the value at Index into a new [`Anon`].
+    pub fn clone_at(&self, index: usize) -> Result<Anon> {
+        if index >= self.len {
+            start_trace!(EcsError::IndexOutOfBounds { index, len: self.len })
         }
+
+        let Some(clone) = self.info.clone else {
+            start_trace!(EcsError::NotCloneable(self.info.name))
+        };
+
+        let anon = Anon::alloc(self.info);
+        unsafe { clone(self.inner.as_ptr().add(self.info.layout.size() * index), anon.as_ptr()) };
+
+        Ok(anon)
     }
 
-    pub fn index_cast<T>(&mut self, index: usize) -> &'static mut T 
+    pub fn index_cast<T>(&mut self, index: usize) -> Result<&mut T> 
     where
         T: Component
     {
-        unsafe {
-            if index >= self.len {
-                panic!("Index ({0}) must be less than the len! (len: ({1})", index, self.len);
-            }
-
-            let size = self.layout.size();
+        trace!(self.info.check::<T>());
 
-            &mut *(self.inner.as_ptr().add(size * index).cast::<T>())
+        if index >= self.len {
+            start_trace!(EcsError::IndexOutOfBounds { index, len: self.len })
         }
+
+        let size = self.info.layout.size();
+
+        unsafe { Ok(&mut *(self.inner.as_ptr().add(size * index).cast::<T>())) }
     }
 
-    /// Swaps the last element with the element at Index, then destroys the value.
-    pub fn destroy_swap(&mut self, index: usize) {
+    /// Drops the value at Index, then moves the last element into its place.
+    pub fn destroy_swap(&mut self, index: usize) -> Result<()> {
+        if index >= self.len {
+            start_trace!(EcsError::IndexOutOfBounds { index, len: self.len })
+        }
+
         unsafe {
-            // panic on out of bounds
-            if index >= self.len {
-                panic!("Index ({0}) must be less than the len! (len: {1})", index, self.len);
-            }
+            let ptr = self.inner.as_ptr().add(self.info.layout.size() * index);
 
-            let size = self.layout.size();
-
-            // if this is the last element, drop and decrement.
-            if index == self.len - 1 {
-                // Drop the value inside if it needs it using the
-                // function we created for it earlier in Anon.
-                if let Some(drop) = self.drop {
-                    drop(self.inner.as_ptr().add(size * self.len));
-                }
-                // decrement to overwrite the value
-                self.len -= 1;
-                return;
+            if let Some(on_remove) = self.info.hooks.on_remove {
+                on_remove(ptr);
             }
 
-            // location to copy from (last element)
-            let src = self.inner.as_ptr().add(size * self.len);
-            // location at index
-            let dst = self.inner.as_ptr().add(size * index);
-
             // Drop the value inside if it needs it using the
-            // function we created for it earlier. 
-            if let Some(drop) = self.drop {
-                drop(self.inner.as_ptr().add(size * self.len));
+            // function we created for it earlier in Anon.
+            if let Some(drop) = self.info.drop {
+                drop(ptr);
             }
 
-            // perform the copy to overwrite the memory
-            ptr::copy(src, dst, size);
-
-            self.len -= 1;
+            self.remove_swap(index);
         }
+
+        Ok(())
     }
 
-    pub fn destroy_nodrop(&mut self, index: usize) {
-        unsafe {
-            // panic on out of bounds
-            if index >= self.len {
-                panic!("Index ({0}) must be less than the len! (len: {1})", index, self.len);
-            }
+    /// Moves the last element into Index without dropping the value that was there.
+    /// Used after the value has been moved out with `read`. 
+    pub fn destroy_nodrop(&mut self, index: usize) -> Result<()> {
+        if index >= self.len {
+            start_trace!(EcsError::IndexOutOfBounds { index, len: self.len })
+        }
 
-            let size = self.layout.size();
+        unsafe { self.remove_swap(index) };
 
-            // if this is the last element, drop and decrement.
-            if index == self.len - 1 {
-                // decrement to overwrite the value
-                self.len -= 1;
-                return;
-            }
+        Ok(())
+    }
 
+    /// Overwrites Index with the last element and decrements the length.
+    /// Never drops anything. 
+    unsafe fn remove_swap(&mut self, index: usize) {
+        let size = self.info.layout.size();
+        let last = self.len - 1;
+
+        // if this is not the last element, move the last element into its slot.
+        if index != last {
             // location to copy from (last element)
-            let src = self.inner.as_ptr().add(size * self.len);
+            let src = self.inner.as_ptr().add(size * last);
             // location at index
             let dst = self.inner.as_ptr().add(size * index);
 
             // perform the copy to overwrite the memory
-            ptr::copy(src, dst, size);
+            ptr::copy_nonoverlapping(src, dst, size);
+        }
+
+        // decrement to forget the (now duplicated) last element
+        self.len = last;
+    }
+
+    /// Calls the `on_add` hook on every value from `start` to the end of the vector.
+    pub(crate) fn on_add(&mut self, start: usize) {
+        if let Some(on_add) = self.info.hooks.on_add {
+            let size = self.info.layout.size();
 
-            self.len -= 1;
+            for i in start..self.len {
+                unsafe { on_add(self.inner.as_ptr().add(size * i)) }
+            }
         }
     }
 
-    pub fn iter_as<T>(&self) -> AnonIter<T> 
+    /// Iterates over the values as `T`, failing unless they are `T`s. The references 
+    /// can't outlive the borrow of the vector, so they are gone before it can reallocate.
+    pub fn iter_as<T>(&self) -> Result<std::slice::Iter<'_, T>> 
+    where
+        T: Component
+    {
+        trace!(self.info.check::<T>());
+
+        // the values are `T`s laid out one after the other, and the pointer is
+        // dangling but aligned while there are none.
+        Ok(unsafe { std::slice::from_raw_parts(self.inner.as_ptr().cast::<T>(), self.len) }.iter())
+    }
+
+    /// Iterates over the values as mutable `T`s, failing unless they are `T`s.
+    pub fn iter_as_mut<T>(&mut self) -> Result<std::slice::IterMut<'_, T>> 
+    where
+        T: Component
+    {
+        trace!(self.info.check::<T>());
+
+        Ok(unsafe { std::slice::from_raw_parts_mut(self.inner.as_ptr().cast::<T>(), self.len) }.iter_mut())
+    }
+
+    /// Iterates over the values as mutable `T`s through a shared borrow, for queries.
+    ///
+    /// # Safety
+    /// Nothing else may reference the values while the items are alive.
+    pub(crate) unsafe fn iter_unchecked<T>(&self) -> Result<AnonIter<'_, T>> 
     where
         T: Component
     {
-        AnonIter {
+        trace!(self.info.check::<T>());
+
+        Ok(AnonIter {
             ptr: self.inner.as_ptr().cast::<T>(),
             curr: 0,
             len: self.len,
-        }
+            marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 
     unsafe fn grow_if_full(&mut self) {
@@ -172,37 +445,55 @@ impl AnonVec {
         let available_space = self.capacity - self.len;
 
         // If there is no available space, double it.
-        // Also, don't allocate anything if this is a ZST.
-        if available_space == 0 && self.layout.size() != 0 {
+        // ZSTs have a capacity of usize::MAX, so they never get here.
+        if available_space == 0 {
             // Double the current capacity
-            let new_capacity = self.capacity * 2;
-            // Create a layout by duplicating an item for n times
-            let new_layout = Layout::from_size_align(
-                self.layout.size() * new_capacity,
-                self.layout.align(),
-            ).unwrap();
-
-            // Reassign self.data. 
-            let new_data;
-                if self.capacity == 0 {
-                    // if uninit, init
-                    new_data = alloc(new_layout);
-                } else {
-                    new_data = realloc(self.inner.as_ptr(), new_layout, new_capacity * self.layout.size());
-                };
-            
-            self.inner = NonNull::new(new_data).unwrap();
-            self.capacity = new_capacity;
+            let new_capacity = if self.capacity == 0 { 4 } else { self.capacity * 2 };
+            self.realloc(new_capacity);
+        }
+    }
+
+    /// Moves the buffer into an allocation that fits exactly `new_capacity` elements.
+    unsafe fn realloc(&mut self, new_capacity: usize) {
+        let new_layout = array_layout(self.info.layout, new_capacity);
+
+        // Reassign self.data. 
+        let new_data = if self.capacity == 0 {
+            // if uninit, init
+            alloc(new_layout)
+        } else {
+            realloc(self.inner.as_ptr(), array_layout(self.info.layout, self.capacity), new_layout.size())
+        };
+
+        self.inner = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(new_layout));
+        self.capacity = new_capacity;
+    }
+
+    /// Releases any capacity that is not being used. 
+    pub fn shrink_to_fit(&mut self) {
+        // ZSTs and already-tight buffers have nothing to give back.
+        if self.info.layout.size() == 0 || self.capacity == self.len {
+            return;
+        }
+
+        unsafe {
+            if self.len == 0 {
+                dealloc(self.inner.as_ptr(), array_layout(self.info.layout, self.capacity));
+                self.inner = dangling(self.info.layout);
+                self.capacity = 0;
+            } else {
+                self.realloc(self.len);
+            }
         }
     }
 
     pub fn clear(&mut self) {
         let len = self.len;
-        let size = self.layout.size();
+        let size = self.info.layout.size();
 
         self.len = 0;
 
-        if let Some(drop) = self.drop {
+        if let Some(drop) = self.info.drop {
             for i in 0..len {
                 unsafe { drop(self.inner.as_ptr().add(i * size)) }
             }
@@ -210,11 +501,23 @@ impl AnonVec {
     }
 }
 
+impl Drop for AnonVec {
+    fn drop(&mut self) {
+        self.clear();
+
+        if self.info.layout.size() != 0 && self.capacity != 0 {
+            unsafe { dealloc(self.inner.as_ptr(), array_layout(self.info.layout, self.capacity)) }
+        }
+    }
+}
+
+/// Anonymously-Typed Box
+/// 
+/// Owns a single heap allocated value, which is dropped and freed 
+/// along with the `Anon` unless it is moved into an [`AnonVec`].
 pub struct Anon {
     inner: NonNull<u8>,
-    drop: Option<fn(*mut u8)>,
-    cmpid: ComponentId,
-    layout: Layout,
+    info: ComponentInfo,
 }
 
 impl Anon {
@@ -222,75 +525,154 @@ impl Anon {
     where
         T: Component,
     {
-        fn drop_as<T>(ptr: *mut u8) {
-            unsafe {
-                ptr.cast::<T>().drop_in_place();
-            }
-        }
+        let anon = Self::alloc(ComponentInfo::of::<T>());
 
-        unsafe {
-            let layout = Layout::new::<T>();
-            let ptr = NonNull::new(alloc(layout)).unwrap();
-            let drop: Option<fn(*mut u8)> = 
-                if needs_drop::<T>() { Some(drop_as::<T>) } else { None };
-
-            ptr::write(ptr.as_ptr().cast::<T>(), val);
-
-            Self {
-                inner: ptr,
-                drop,
-                cmpid: *T::handle(),
-                layout,
-            }
-        }
+        unsafe { ptr::write(anon.as_ptr().cast::<T>(), val) };
+
+        anon
     }
 
-    pub fn uninit() -> Self {
+    /// Allocates space for a value without initializing it.
+    /// The caller must write a value before the `Anon` is dropped.
+    pub(crate) fn alloc(info: ComponentInfo) -> Self {
+        let layout = info.layout;
+
+        let inner = if layout.size() == 0 {
+            dangling(layout)
+        } else {
+            unsafe { NonNull::new(alloc(layout)).unwrap_or_else(|| handle_alloc_error(layout)) }
+        };
+
         Self {
-            inner: NonNull::dangling(),
-            drop: None,
-            cmpid: 0,
-            layout: Layout::new::<i32>(),
+            inner,
+            info,
         }
     }
 
+    /// Moves the value behind `src` into a new `Anon`.
+    /// 
+    /// # Safety
+    /// `src` must point to a valid value of the component described by `info`,
+    /// which the `Anon` takes ownership of. The value must be safe to send to and
+    /// share between threads, which holds for every typed component and is up to
+    /// the creator of a dynamic one. 
+    pub unsafe fn from_raw(info: ComponentInfo, src: *const u8) -> Self {
+        let anon = Self::alloc(info);
+        ptr::copy_nonoverlapping(src, anon.as_ptr(), info.layout.size());
+        anon
+    }
+
     pub fn id(&self) -> ComponentId {
-        self.cmpid
+        self.info.id
     }
 
-    pub fn downcast<T>(&self) -> &T 
+    pub fn info(&self) -> &ComponentInfo {
+        &self.info
+    }
+
+    /// Sets the id the component has in the Ecs that owns `components`.
+    pub(crate) fn resolve(&mut self, components: &Components) -> Result<()> {
+        components.resolve(&mut self.info)
+    }
+
+    /// The value as a `T`, failing unless it is one.
+    pub fn downcast<T>(&self) -> Result<&T> 
     where
         T: Component,
     {
-        unsafe { &*self.inner.as_ptr().cast::<T>() }
+        trace!(self.info.check::<T>());
+
+        unsafe { Ok(&*self.inner.as_ptr().cast::<T>()) }
     }
 
-    pub fn downcast_mut<T>(&self) -> &mut T 
+    pub fn downcast_mut<T>(&mut self) -> Result<&mut T> 
     where
         T: Component,
     {
-        unsafe { &mut *self.inner.as_ptr().cast::<T>() }
+        trace!(self.info.check::<T>());
+
+        unsafe { Ok(&mut *self.inner.as_ptr().cast::<T>()) }
     }
 
     pub fn as_ptr(&self) -> *mut u8 {
         self.inner.as_ptr()
     }
 
-    pub fn clear(&self) {
-        if let Some(drop) = self.drop {
-            drop(self.as_ptr())
+    /// Clones the value into a new `Anon`, if the component can be cloned.
+    pub fn try_clone(&self) -> Result<Anon> {
+        let Some(clone) = self.info.clone else {
+            start_trace!(EcsError::NotCloneable(self.info.name))
+        };
+
+        let anon = Anon::alloc(self.info);
+        unsafe { clone(self.as_ptr(), anon.as_ptr()) };
+
+        Ok(anon)
+    }
+
+    /// The field at `path` of a reflected component, such as `"velocity.x"`.
+    pub fn field<T: Reflect>(&self, path: &str) -> Result<&T> {
+        unsafe { Ok(&*trace!(reflect::field_ptr::<T>(self.as_ptr(), &self.info, path))) }
+    }
+
+    pub fn field_mut<T: Reflect>(&mut self, path: &str) -> Result<&mut T> {
+        unsafe { Ok(&mut *trace!(reflect::field_ptr::<T>(self.as_ptr(), &self.info, path))) }
+    }
+
+    pub(crate) fn on_add(&self) {
+        if let Some(on_add) = self.info.hooks.on_add {
+            on_add(self.as_ptr())
+        }
+    }
+
+    pub(crate) fn on_remove(&self) {
+        if let Some(on_remove) = self.info.hooks.on_remove {
+            on_remove(self.as_ptr())
+        }
+    }
+
+    /// Frees the allocation without dropping the value inside.
+    /// Used once the value has been moved somewhere else.
+    pub(crate) fn forget(self) {
+        let anon = ManuallyDrop::new(self);
+
+        if anon.info.layout.size() != 0 {
+            unsafe { dealloc(anon.as_ptr(), anon.info.layout) }
         }
     }
 }
 
-pub struct AnonIter<T: 'static> {
+impl Drop for Anon {
+    fn drop(&mut self) {
+        if let Some(drop) = self.info.drop {
+            unsafe { drop(self.as_ptr()) }
+        }
+
+        if self.info.layout.size() != 0 {
+            unsafe { dealloc(self.as_ptr(), self.info.layout) }
+        }
+    }
+}
+
+/// A non-null pointer aligned for `layout`, used by ZSTs and empty vectors.
+fn dangling(layout: Layout) -> NonNull<u8> {
+    NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap()
+}
+
+/// The layout of `n` values of `layout` laid out back to back.
+fn array_layout(layout: Layout, n: usize) -> Layout {
+    Layout::from_size_align(layout.size() * n, layout.align()).unwrap()
+}
+
+pub struct AnonIter<'a, T> {
     pub(crate) ptr: *mut T,
     pub(crate) curr: usize,
     pub(crate) len: usize,
+    marker: PhantomData<&'a mut T>,
 }
 
-impl<T> Iterator for AnonIter<T> {
-    type Item = &'static mut T;
+impl<'a, T> Iterator for AnonIter<'a, T> {
+    type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.curr == self.len {
@@ -302,11 +684,11 @@ impl<T> Iterator for AnonIter<T> {
     }
 }
 
-pub struct AnonIterChain<T: 'static> {
-    pub iters: Vec<AnonIter<T>>,
+pub struct AnonIterChain<'a, T> {
+    pub iters: Vec<AnonIter<'a, T>>,
 }
 
-impl<T> AnonIterChain<T> {
+impl<'a, T> AnonIterChain<'a, T> {
     pub fn is_empty(&self) -> bool {
         self.iters.is_empty()
     }
@@ -323,7 +705,7 @@ impl<T> AnonIterChain<T> {
         }
     }
 
-    pub fn push(&mut self, iter: AnonIter<T>) {
+    pub fn push(&mut self, iter: AnonIter<'a, T>) {
         self.iters.push(iter);
     }
 
@@ -336,8 +718,14 @@ impl<T> AnonIterChain<T> {
     }
 }
 
-impl<T> Iterator for AnonIterChain<T> {
-    type Item = &'static mut T;
+impl<T> Default for AnonIterChain<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> Iterator for AnonIterChain<'a, T> {
+    type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
         // get the last iter if it exists
@@ -357,5 +745,10 @@ impl<T> Iterator for AnonIterChain<T> {
     }
 }
 
+// Both only ever hold values of components. Typed components are `Send + Sync` by the
+// bounds of `Component`, and dynamic ones promise to be in `ComponentInfo::dynamic`.
+// This is what makes tables, and through them the Ecs, safe to share with systems.
 unsafe impl Send for Anon {}
-unsafe impl Sync for Anon {}
\ No newline at end of file
+unsafe impl Sync for Anon {}
+unsafe impl Send for AnonVec {}
+unsafe impl Sync for AnonVec {}
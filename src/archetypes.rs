@@ -1,18 +1,21 @@
 
 use std::collections::BTreeMap;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 use rayon::prelude::*;
 use indexmap::IndexSet;
 use super::handle::Component;
-use anyhow::{Result, anyhow};
 use super::commands::Commands;
 use super::table::Table;
-use super::table::{DO_DROP, NO_DROP};
+use super::table::DO_DROP;
 use super::anon::AnonIterChain;
 use super::package::PackageIndexChain;
-use super::error::Trace;
-use super::start_trace;
+use super::package::{PackageIndex, Reserved};
+use super::package::Package;
+use super::bundle::{Bundle, DynamicBundle, archetype_of};
+use super::components::{Components, resolve_required};
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
 
 pub type Column = usize;
 pub type TableIndex = usize;
@@ -20,113 +23,318 @@ pub type ComponentId = u16;
 
 pub struct Archetypes {
     archetypes: BTreeMap<Archetype, TableIndex>,
-    commands: Commands,
+    commands: Mutex<Commands>,
     tables: Vec<Table>,
     cache: QueryCache,
+    /// How many times the queued commands have been flushed.
+    flushes: u64,
+    /// The table and first column of the entities of each archetype spawned by the last flush.
+    spawned: BTreeMap<Archetype, (TableIndex, Column)>,
 }
 
 impl Archetypes {
     pub const fn new() -> Self {
         Self {
-            /// Fast lookup for inserting Archetypes into Tables. 
+            // Fast lookup for inserting Archetypes into Tables. 
             archetypes: BTreeMap::new(),
 
-            /// Command Queue. Commands can be submitted and stored
-            /// here before being processed. 
-            commands: Commands::null(),
+            // Command Queue. Commands can be submitted and stored
+            // here before being processed. Systems running in parallel
+            // submit to it at the same time, so it is behind a lock.
+            commands: Mutex::new(Commands::null()),
 
-            /// Stores the tables, which stores the components
-            /// for a specific archetype. 
+            // Stores the tables, which stores the components
+            // for a specific archetype. 
             tables: Vec::new(),
 
-            /// Cache for storing archetypes that are parents
-            /// of Queries. Updated whenever a new table is allocated. 
+            // Cache for storing archetypes that are parents
+            // of Queries. Updated whenever a new table is allocated. 
             cache: QueryCache::new(),
+
+            // Where the entities reserved by commands landed in the last flush.
+            flushes: 0,
+            spawned: BTreeMap::new(),
         }
     }
 
-    pub fn load_query(&mut self, key: Archetype, ids: Vec<ComponentId>) -> Trace<()> {
-        self.cache.load(key, ids)
+    pub fn load_query(&mut self, key: Archetype, ids: Vec<ComponentId>) -> Result<()> {
+        self.cache.load(key, ids, &self.tables)
     }
 
-    pub fn query_cache(&self, key: Archetype) -> Trace<&IndexSet<TableIndex>> {
+    pub fn query_cache(&self, key: Archetype) -> Result<&IndexSet<TableIndex>> {
         self.cache.search(key)
     }
 
-    pub fn submit_commands(&mut self, mut commands: Commands) -> Trace<()> {
-        while let Some((key, packages)) = commands.spawn.pop_first() {
-            if let Some(spawn) = self.commands.spawn.get_mut(&key) {
-                spawn.extend(packages);
-            } else {
-                if let Some(_) = self.commands.spawn.insert(key, packages) {
-                    start_trace!("Attempted to insert the same key into spawn twice!, (internal error, contact maintainer)");
-                }
-            }
+    /// How many entities a registered query matches, or 0 if it isn't registered.
+    pub fn count(&self, key: Archetype) -> usize {
+        match self.cache.search(key) {
+            Ok(indices) => indices.iter().map(|index| self.tables[*index].len()).sum(),
+            Err(_) => 0,
         }
+    }
+
+    pub fn submit_commands(&self, mut commands: Commands) -> Result<()> {
+        let mut queue = self.queue();
 
         while let Some((table, columns)) = commands.destroy.pop_first() {
-            if let Some(destroy) = self.commands.destroy.get_mut(&table) {
+            if let Some(destroy) = queue.destroy.get_mut(&table) {
                 destroy.extend(columns);
             } else {
-                if let Some(_) = self.commands.destroy.insert(table, columns) {
-                    start_trace!("Tried to insert the same destroy request twice! (internal error, contact maintainer)")
+                if queue.destroy.insert(table, columns).is_some() {
+                    start_trace!(EcsError::Internal("inserted the same destroy request twice"))
                 }
             }
         }
 
         while let Some((index, modifies)) = commands.modify.pop_first() {
-            if let Some(modify) = self.commands.modify.get_mut(&index) {
+            if let Some(modify) = queue.modify.get_mut(&index) {
                 modify.extend(modifies);
             } else {
-                if let Some(_) = self.commands.modify.insert(index, modifies) {
-                    start_trace!("Tried modify the same Modify request twice (internal error, contact maintaner)")
+                if queue.modify.insert(index, modifies).is_some() {
+                    start_trace!(EcsError::Internal("inserted the same modify request twice"))
                 }
             }
         }
 
-        Trace::Ok(())
+        queue.clone.append(&mut commands.clone);
+
+        Ok(())
+    }
+
+    /// Queues the entities in `table` to be spawned into the table for `key` 
+    /// at the next flush, returning their reservations in order.
+    pub(crate) fn reserve(&self, key: Archetype, mut table: Table) -> Result<Vec<Reserved>> {
+        let mut queue = self.queue();
+        let position = queue.spawn.iter().position(|(other, _)| *other == key);
+        let start = position.map_or(0, |i| queue.spawn[i].1.len());
+
+        let len = table.len();
+        match position {
+            Some(i) => trace!(queue.spawn[i].1.append(&mut table)),
+            None => queue.spawn.push((key, table)),
+        }
+
+        Ok((start..start + len).map(|offset| Reserved { flush: self.flushes, archetype: key, offset }).collect())
+    }
+
+    /// Where an entity reserved by commands was spawned. Fails until the flush that
+    /// spawns it, and from the flush after that on, as entities may have moved since.
+    pub fn resolve(&self, reserved: Reserved) -> Result<PackageIndex> {
+        if reserved.flush + 1 != self.flushes {
+            start_trace!(EcsError::UnresolvedSpawn)
+        }
+
+        match self.spawned.get(&reserved.archetype) {
+            Some((table, start)) => Ok(PackageIndex { table: *table, col: start + reserved.offset }),
+            None => start_trace!(EcsError::Internal("a reserved entity was not spawned by its flush")),
+        }
+    }
+
+    /// Whether any commands have been submitted or spawns reserved since the last flush.
+    pub fn has_queued(&self) -> bool {
+        !self.queue().is_empty()
+    }
+
+    fn queue(&self) -> MutexGuard<'_, Commands> {
+        // the queue is only ever appended to while locked, so it is whole even if a holder panicked.
+        self.commands.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    pub fn flush_queues(&mut self) {
-        while let Some((index, modify)) = self.commands.modify.pop_first() {
-            if let Some(columns) = self.commands.destroy.get_mut(&index.table) {
-                if !columns.contains(&(index.col, DO_DROP)) {
-                    let package = self.tables[index.table].extract(modify, index.col);
-                    self.commands.spawn_package(package);
-                    self.commands.destroy_nodrop(index);
-                } 
+    fn queued(&mut self) -> &mut Commands {
+        self.commands.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies every submitted command, with component ids from `components`.
+    pub fn flush_queues(&mut self, components: &Components) -> Result<()> {
+        // Clones read the entities where they are now, before anything moves them.
+        for index in std::mem::take(&mut self.queued().clone) {
+            trace!(self.clone_entity(index, components));
+        }
+
+        while let Some((index, modify)) = self.queued().modify.pop_first() {
+            // Entities that are being destroyed are not worth moving,
+            // their components will be dropped along with the modify.
+            if let Some(columns) = self.queued().destroy.get(&index.table) {
+                if columns.contains(&(index.col, DO_DROP)) {
+                    continue;
+                }
             }
+
+            // Check the archetype the entity moves to before anything is moved out of it,
+            // since a failure once the package owns the components would drop them 
+            // along with the package and again along with the column.
+            trace!(archetype_of(&self.tables[index.table].infos_after(&modify)));
+
+            // Move the entity into a package, then forget the old column 
+            // without dropping it, since the package now owns the components.
+            let mut package = trace!(self.tables[index.table].extract(modify, index.col));
+            self.queued().destroy_nodrop(index);
+
+            trace!(package.resolve(components));
+            trace!(self.move_package(package));
         }
 
-        while let Some((key, packages)) = self.commands.spawn.pop_first() {
-            if let Some(index) = self.archetypes.get(&key) {
-                self.tables[*index].spawn(packages);
+        while let Some((index, destroys)) = self.queued().destroy.pop_first() {
+            match self.tables.get_mut(index) {
+                Some(table) => trace!(table.destroy(destroys)),
+                None => start_trace!(EcsError::InvalidEntity { table: index, col: destroys[0].0 }),
+            }
+        }
+
+        // Spawns come last, as destroying swaps entities into the columns that are
+        // left, so only now is it known where the reserved entities land.
+        self.spawned.clear();
+        let spawn = std::mem::take(&mut self.queued().spawn);
+        for (key, mut spawned) in spawn {
+            if let Some(index) = self.archetypes.get(&key).copied() {
+                let table = &mut self.tables[index];
+                let start = table.len();
+                trace!(table.append(&mut spawned));
+                table.on_add(start);
+                self.spawned.insert(key, (index, start));
             } else {
-                let len = self.tables.len();
-                if let None = self.archetypes.insert(key, len) {
-                    self.tables.push(Table::new(packages));
-                    self.cache.update(len, &self.tables[len]);
-                } else {
-                    panic!("Attempted to insert a duplicate archetype!")
-                }
+                // the staging table becomes the archetype's table.
+                let index = trace!(self.insert_table(key, spawned));
+                self.tables[index].on_add(0);
+                self.spawned.insert(key, (index, 0));
             }
         }
 
-        while let Some((index, destroys)) = self.commands.destroy.pop_first() {
-            self.tables[index].destroy(destroys);
+        self.flushes += 1;
+
+        Ok(())
+    }
+
+    /// Spawns an entity for every bundle in `iter` directly into the table of
+    /// its archetype, returning the index of each new entity in order.
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, iter: I, components: &Components) -> Result<Vec<PackageIndex>> {
+        let mut infos = Vec::new();
+        B::infos(&mut infos);
+        let required = resolve_required(&mut infos);
+        trace!(components.resolve_all(&mut infos));
+
+        let key = trace!(archetype_of(&infos));
+
+        let index = match self.archetypes.get(&key) {
+            Some(index) => *index,
+            None => trace!(self.insert_table(key, Table::new(&infos))),
+        };
+
+        // Entities are only ever appended, so any queued commands 
+        // that point into this table are still valid afterwards.
+        let table = &mut self.tables[index];
+        let start = table.len();
+        trace!(table.push_batch(iter, &infos, &required));
+        table.on_add(start);
+
+        Ok((start..table.len())
+            .map(|col| PackageIndex { table: index, col })
+            .collect())
+    }
+
+    /// Spawns a single entity directly into the table of its archetype.
+    pub fn spawn<B: DynamicBundle>(&mut self, bundle: B, components: &Components) -> Result<PackageIndex> {
+        let mut infos = Vec::new();
+        bundle.infos_dyn(&mut infos);
+        let required = resolve_required(&mut infos);
+        trace!(components.resolve_all(&mut infos));
+
+        let key = trace!(archetype_of(&infos));
+
+        let index = match self.archetypes.get(&key) {
+            Some(index) => *index,
+            None => trace!(self.insert_table(key, Table::new(&infos))),
+        };
+
+        let table = &mut self.tables[index];
+        let col = table.len();
+        trace!(table.push(bundle, &infos, &required));
+        table.on_add(col);
+
+        Ok(PackageIndex { table: index, col })
+    }
+
+    /// Spawns a copy of the entity at `index` into the same table,
+    /// returning the index of the copy.
+    pub fn clone_entity(&mut self, index: PackageIndex, components: &Components) -> Result<PackageIndex> {
+        trace!(self.validate(index));
+
+        let package = trace!(self.tables[index.table].clone_column(index.col));
+        self.spawn(package, components)
+    }
+
+    /// Pushes an entity that is moving between archetypes straight into its new table.
+    /// Every component of the package must already be resolved. Unlike spawning,
+    /// this does not call any `on_add` hooks. 
+    fn move_package(&mut self, package: Package) -> Result<()> {
+        let mut infos = Vec::new();
+        package.infos_dyn(&mut infos);
+
+        let key = trace!(archetype_of(&infos));
+
+        let index = match self.archetypes.get(&key) {
+            Some(index) => *index,
+            None => trace!(self.insert_table(key, Table::new(&infos))),
+        };
+
+        self.tables[index].push(package, &infos, &[])
+    }
+
+    /// Adds a table for a new archetype and lets the query cache know about it.
+    fn insert_table(&mut self, key: Archetype, table: Table) -> Result<TableIndex> {
+        let len = self.tables.len();
+        if self.archetypes.insert(key, len).is_some() {
+            start_trace!(EcsError::Internal("inserted a table for an archetype that already had one"))
+        }
+
+        self.tables.push(table);
+        self.cache.update(len, &self.tables[len]);
+        Ok(len)
+    }
+
+    /// Errors unless `index` points at an entity that exists right now.
+    pub fn validate(&self, index: PackageIndex) -> Result<()> {
+        match self.tables.get(index.table) {
+            Some(table) if index.col < table.len() => Ok(()),
+            _ => start_trace!(EcsError::InvalidEntity { table: index.table, col: index.col }),
+        }
+    }
+
+    /// A pointer to component `id` of the entity at `index`, 
+    /// or `None` if the entity doesn't have one.
+    pub fn get_raw(&self, index: PackageIndex, id: ComponentId) -> Result<Option<*mut u8>> {
+        trace!(self.validate(index));
+
+        Ok(self.tables[index.table].row(id).map(|row| {
+            unsafe { row.as_ptr().add(row.info().layout.size() * index.col) }
+        }))
+    }
+
+    pub(crate) fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    /// Releases unused memory held by every table. 
+    pub fn compact(&mut self) {
+        for table in self.tables.iter_mut() {
+            table.shrink_to_fit();
         }
     }
 
-    pub fn collect<C: Component>(&self, indices: &IndexSet<TableIndex>) -> AnonIterChain<C> {
+    /// Iterates over the row of `C` in every table in `indices`.
+    ///
+    /// # Safety
+    /// Nothing else may reference the components while the items are alive.
+    pub(crate) unsafe fn collect<C: Component>(&self, indices: &IndexSet<TableIndex>, id: ComponentId) -> Result<AnonIterChain<'_, C>> {
         let mut chain = AnonIterChain { iters: Vec::with_capacity(indices.len()) };
 
         for index in indices.iter() {
-            if let Some(iter) = self.tables[*index].collect::<C>() {
+            if let Some(iter) = trace!(self.tables[*index].collect::<C>(id)) {
                 chain.push(iter);
             }
         }
 
-        chain
+        Ok(chain)
     }
 
     pub fn collect_indices(&self, indices: &IndexSet<TableIndex>) -> PackageIndexChain {
@@ -142,7 +350,13 @@ impl Archetypes {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+impl Default for Archetypes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Archetype(u64);
 
 impl Archetype {
@@ -151,10 +365,24 @@ impl Archetype {
     }
 
     pub const fn add(mut self, id: u64) -> Self {
+        // offset by one, or the component with id 0 would hash to nothing
+        // and be left out of every archetype it is in.
+        let id = id.wrapping_add(1);
         let h = id.wrapping_mul(123456789123456789);
         self.0 = self.0.wrapping_add(id.wrapping_add(h.wrapping_shl((h % 32) as u32 + 1)));
         self
     }
+
+    /// The archetype of exactly the components `ids`, in any order.
+    pub fn of(ids: &[ComponentId]) -> Self {
+        ids.iter().fold(Self::new(), |key, id| key.add(*id as u64))
+    }
+}
+
+impl Default for Archetype {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct QueryCache {
@@ -176,29 +404,38 @@ impl QueryCache {
         });
     }
 
-    pub fn search(&self, key: Archetype) -> Trace<&IndexSet<TableIndex>> {
+    pub fn search(&self, key: Archetype) -> Result<&IndexSet<TableIndex>> {
         if let Some((_, indices)) = self.cache.get(&key) {
-            Trace::Ok(indices)
+            Ok(indices)
         } else {
-            start_trace!(format!("Tried to search the Query Cache for a Query that does not exist!"))
+            start_trace!(EcsError::QueryNotRegistered)
         }
     }
 
-    pub fn load(&mut self, key: Archetype, ids: Vec<ComponentId>) -> Trace<()> {
+    /// Registers a query, matching it against every table that already exists.
+    pub fn load(&mut self, key: Archetype, ids: Vec<ComponentId>, tables: &[Table]) -> Result<()> {
         if let Some((exists, _)) = self.cache.get(&key) {
             for id in ids.iter() {
                 if !exists.contains(id) {
-                    start_trace!("
-                        Same Archetype but different components! 
-                        Indicates hashing algorithm is bad. 
-                        (internal error, contact maintaner)
-                    ");
+                    start_trace!(EcsError::Internal("two queries with different components hashed to the same archetype"));
                 }
             }
         } else {
-            self.cache.insert(key, (ids, IndexSet::new()));
+            let indices = tables.iter()
+                .enumerate()
+                .filter(|(_, table)| table.contains(&ids))
+                .map(|(index, _)| index)
+                .collect();
+
+            self.cache.insert(key, (ids, indices));
         }
 
-        Trace::Ok(())
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,126 @@
+
+use super::anon::ComponentInfo;
+use super::archetypes::Archetype;
+use super::handle::Component;
+use super::package::Package;
+use super::error::{EcsError, Result};
+use super::start_trace;
+
+/// A statically known group of components that are spawned,
+/// inserted or removed together.
+///
+/// Every [`Component`] is a bundle of one, tuples of bundles are bundles,
+/// and structs can derive it with `#[derive(Bundle)]`, where every field
+/// is either a component or another bundle.
+///
+/// # Safety
+/// `take` must hand `func` exactly one pointer for every info pushed by `infos`,
+/// in the same order, each to a valid value of that component that `func` then owns.
+pub unsafe trait Bundle: 'static {
+    /// Pushes the info of every component in the bundle,
+    /// in the same order that `take` visits them.
+    fn infos(infos: &mut Vec<ComponentInfo>);
+
+    /// Moves every component out of the bundle, handing a pointer to each one
+    /// to `func`. `func` takes ownership of the value behind the pointer.
+    fn take(self, func: &mut dyn FnMut(*mut u8));
+}
+
+unsafe impl<C: Component> Bundle for C {
+    fn infos(infos: &mut Vec<ComponentInfo>) {
+        infos.push(ComponentInfo::of::<C>());
+    }
+
+    fn take(self, func: &mut dyn FnMut(*mut u8)) {
+        let mut cmp = std::mem::ManuallyDrop::new(self);
+        func((&mut *cmp as *mut C).cast::<u8>());
+    }
+}
+
+macro_rules! impl_bundle {
+    ($($name:ident),*) => {
+        unsafe impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+            fn infos(infos: &mut Vec<ComponentInfo>) {
+                $($name::infos(infos);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn take(self, func: &mut dyn FnMut(*mut u8)) {
+                let ($($name,)*) = self;
+                $($name.take(func);)*
+            }
+        }
+    };
+}
+
+impl_bundle!(B1);
+impl_bundle!(B1, B2);
+impl_bundle!(B1, B2, B3);
+impl_bundle!(B1, B2, B3, B4);
+impl_bundle!(B1, B2, B3, B4, B5);
+impl_bundle!(B1, B2, B3, B4, B5, B6);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7, B8);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7, B8, B9);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7, B8, B9, B10);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11);
+impl_bundle!(B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11, B12);
+
+/// Anything that can be spawned as a single entity, either a
+/// [`Bundle`] or a [`Package`] built at runtime.
+///
+/// # Safety
+/// The same as for [`Bundle`], with `infos_dyn` and `take_dyn`.
+pub unsafe trait DynamicBundle {
+    /// Pushes the info of every component, in the same order that `take_dyn` visits them.
+    fn infos_dyn(&self, infos: &mut Vec<ComponentInfo>);
+
+    /// Moves every component out, handing a pointer to each one to `func`.
+    fn take_dyn(self, func: &mut dyn FnMut(*mut u8));
+}
+
+unsafe impl<B: Bundle> DynamicBundle for B {
+    fn infos_dyn(&self, infos: &mut Vec<ComponentInfo>) {
+        B::infos(infos)
+    }
+
+    fn take_dyn(self, func: &mut dyn FnMut(*mut u8)) {
+        self.take(func)
+    }
+}
+
+unsafe impl DynamicBundle for Package {
+    fn infos_dyn(&self, infos: &mut Vec<ComponentInfo>) {
+        for anon in self.components.iter() {
+            infos.push(*anon.info());
+        }
+    }
+
+    fn take_dyn(self, func: &mut dyn FnMut(*mut u8)) {
+        for anon in self.components {
+            func(anon.as_ptr());
+            // the value was moved out by func, only the box is left to free.
+            anon.forget();
+        }
+    }
+}
+
+/// Computes the archetype of a set of components, failing if any of them
+/// have not been resolved against the Ecs or appear more than once.
+pub(crate) fn archetype_of(infos: &[ComponentInfo]) -> Result<Archetype> {
+    let mut archetype = Archetype::new();
+
+    for (i, info) in infos.iter().enumerate() {
+        if info.id == u16::MAX {
+            start_trace!(EcsError::UnregisteredComponent(info.name));
+        }
+
+        if infos[..i].iter().any(|other| other.id == info.id) {
+            start_trace!(EcsError::DuplicateComponent(info.name));
+        }
+
+        archetype = archetype.add(info.id as u64);
+    }
+
+    Ok(archetype)
+}
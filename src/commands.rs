@@ -1,116 +1,223 @@
 
+use std::any::TypeId;
 use std::collections::BTreeMap;
 
 use super::archetypes::Archetype;
-use super::package::Package;
 use super::archetypes::TableIndex;
 use super::archetypes::Column;
-use super::package::PackageIndex;
+use super::package::{PackageIndex, Reserved};
 use super::table::{DO_DROP, NO_DROP};
 use super::archetypes::ComponentId;
 use super::anon::Anon;
+use super::package::Package;
+use super::prefab::{Prefab, PrefabInstance};
+use super::table::Table;
+use super::bundle::{Bundle, DynamicBundle, archetype_of};
+use super::components::{Components, RequiredComponent, resolve_required};
 use super::handle::Component;
-use super::ptr::Ptr;
+use super::ptr::{Ptr, PtrMut};
 use super::ecs::Ecs;
-use super::params::Fetch;
-use super::error::Trace;
+use super::params::{Fetch, SystemParam};
+use super::params::Locals;
+use super::error::{EcsError, Result};
 use super::scheduler::Accessor;
-use super::trace;
+use super::{start_trace, trace};
 
 pub struct Commands {
     pub(crate) ecs: Ptr<Ecs>,
-    pub(crate) spawn: BTreeMap<Archetype, Vec<Package>>,
+    /// Entities waiting to be spawned, by archetype in the order they were reserved.
+    /// Only the queue in the Ecs holds any, commands reserve theirs there straight away.
+    pub(crate) spawn: Vec<(Archetype, Table)>,
+    /// How many entities these commands have reserved.
+    spawned: usize,
     pub(crate) destroy: BTreeMap<TableIndex, Vec<(Column, bool)>>,
     pub(crate) modify: BTreeMap<PackageIndex, Modify>,
+    pub(crate) clone: Vec<PackageIndex>,
+    // where to count submitted commands while the system is being profiled.
+    counter: Option<PtrMut<usize>>,
 }
 
 impl Commands {
     pub(crate) fn new(ecs: Ptr<Ecs>) -> Self {
         Self {
             ecs,
-            spawn: BTreeMap::new(),
+            spawn: Vec::new(),
+            spawned: 0,
             destroy: BTreeMap::new(),
             modify: BTreeMap::new(),
+            clone: Vec::new(),
+            counter: None,
         }
     }
 
     pub(crate) const fn null() -> Self {
         Self {
             ecs: Ptr::null(),
-            spawn: BTreeMap::new(),
+            spawn: Vec::new(),
+            spawned: 0,
             destroy: BTreeMap::new(),
             modify: BTreeMap::new(),
+            clone: Vec::new(),
+            counter: None,
         }
     }
 
-    pub(crate) fn spawn_package(&mut self, package: Package) {
-        let key = package.archetype();
-
-        if let Some(packages) = self.spawn.get_mut(&key) {
-            packages.push(package);
+    pub(crate) fn destroy_nodrop(&mut self, index: PackageIndex) {
+        if let Some(des) = self.destroy.get_mut(&index.table) {
+            des.push((index.col, NO_DROP));
         } else {
-            if let Some(_) = self.spawn.insert(key, vec![package]) {
-                panic!("Attempted to insert the same key twice!")
-            }
+            self.destroy.insert(index.table, vec![(index.col, NO_DROP)]);
         }
     }
 
-    pub(crate) fn destroy_nodrop(&mut self, index: PackageIndex) {
-        if let Some(des) = self.destroy.get_mut(&index.table) {
-            des.push((index.col, DO_DROP));
-        } else {
-            if let Some(_) = self.destroy.insert(index.table, vec![(index.col, DO_DROP)]) {
-                panic!("Attempted to insert the same key twice! (how did you even do this?)");
-            }
+    /// Spawns a new entity from a [`Bundle`] or [`Package`](super::package::Package).
+    /// The entity is reserved in the Ecs straight away, and spawned when the commands 
+    /// are flushed after the stage, whether or not the commands are submitted.
+    /// Where it lands can be looked up with [`resolve`](Self::resolve) after that.
+    pub fn spawn<B: DynamicBundle>(&mut self, bundle: B) -> Result<Reserved> {
+        let mut infos = Vec::new();
+        bundle.infos_dyn(&mut infos);
+        let required = resolve_required(&mut infos);
+        let components = trace!(self.components());
+        trace!(components.resolve_all(&mut infos));
+
+        let key = trace!(archetype_of(&infos));
+
+        let mut table = Table::new(&infos);
+        trace!(table.push(bundle, &infos, &required));
+
+        let reserved = trace!(self.reserve(key, table));
+        Ok(reserved[0])
+    }
+
+    /// Spawns an instance of `prefab` whose root has the components of `overrides`
+    /// in place of the prefab's own, along with every one of its children.
+    pub fn spawn_prefab<B: DynamicBundle>(&mut self, prefab: &Prefab, overrides: B) -> Result<PrefabInstance<Reserved>> {
+        let root = trace!(self.spawn(trace!(prefab.instantiate(overrides))));
+
+        let mut children = Vec::with_capacity(prefab.children().len());
+        for child in prefab.children() {
+            children.push(trace!(self.spawn_prefab(child, Package::new())));
         }
+
+        Ok(PrefabInstance { root, children })
     }
 
-    pub fn spawn<F>(&mut self, predicate: F) 
-    where   
-        F: Fn() -> Package
-    {
-        self.spawn_package(predicate());
+    /// Spawns an entity for every bundle in `iter`. The bundles are written
+    /// into a staging table column by column, after reserving space once,
+    /// then reserved in the Ecs together, in order.
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, iter: I) -> Result<Vec<Reserved>> {
+        let mut infos = Vec::new();
+        B::infos(&mut infos);
+        let required = resolve_required(&mut infos);
+        let components = trace!(self.components());
+        trace!(components.resolve_all(&mut infos));
+
+        let key = trace!(archetype_of(&infos));
+
+        let mut table = Table::new(&infos);
+        trace!(table.push_batch(iter, &infos, &required));
+
+        self.reserve(key, table)
+    }
+
+    fn reserve(&mut self, key: Archetype, table: Table) -> Result<Vec<Reserved>> {
+        self.spawned += table.len();
+        self.ecs.archetypes.reserve(key, table)
+    }
+
+    /// Where an entity these or any other commands reserved was spawned,
+    /// from the flush that spawns it until the next one.
+    pub fn resolve(&self, reserved: Reserved) -> Result<PackageIndex> {
+        trace!(self.components());
+        self.ecs.archetypes.resolve(reserved)
+    }
+
+    /// The components of the Ecs these commands belong to.
+    fn components(&self) -> Result<&Components> {
+        if self.ecs.is_null() {
+            start_trace!(EcsError::Internal("used commands that do not belong to an Ecs"))
+        }
+
+        Ok(&self.ecs.components)
+    }
+
+    /// Errors unless `index` points at an entity in the Ecs these commands belong to.
+    fn validate(&self, index: PackageIndex) -> Result<()> {
+        if self.ecs.is_null() {
+            start_trace!(EcsError::Internal("used commands that do not belong to an Ecs"))
+        }
+
+        self.ecs.archetypes.validate(index)
     }
 
-    pub fn destroy(&mut self, index: PackageIndex) {
+    pub fn destroy(&mut self, index: PackageIndex) -> Result<()> {
+        trace!(self.validate(index));
+
         if let Some(des) = self.destroy.get_mut(&index.table) {
             des.push((index.col, DO_DROP));
         } else {
-            if let Some(_) = self.destroy.insert(index.table, vec![(index.col, DO_DROP)]) {
-                panic!("Attempted to insert the same key twice! (how did you even do this?)");
-            }
+            self.destroy.insert(index.table, vec![(index.col, DO_DROP)]);
         }
+
+        Ok(())
+    }
+
+    /// Spawns a copy of the entity at `index`, with a clone of every one of its components, 
+    /// into the same archetype. Fails right away if any of the components can't be cloned.
+    /// The components are cloned when the commands are flushed, before any other command.
+    pub fn clone_entity(&mut self, index: PackageIndex) -> Result<()> {
+        trace!(self.validate(index));
+        trace!(self.ecs.archetypes.tables()[index.table].cloneable());
+
+        self.clone.push(index);
+
+        Ok(())
     }
 
-    pub fn modify<F>(&mut self, index: PackageIndex, predicate: F)
+    pub fn modify<F>(&mut self, index: PackageIndex, predicate: F) -> Result<()>
     where
         F: Fn(&mut Modify)
     {
-        if let Some(modify) = self.modify.get_mut(&index) {
-            predicate(modify)
-        } else {
-            let mut modify = Modify::new();
-            predicate(&mut modify);
-            if let Some(_) = self.modify.insert(index, modify) {
-                panic!("Attempted to insert the same key twice!")
-            }
-        }
+        trace!(self.validate(index));
+
+        let modify = self.modify.entry(index).or_insert_with(Modify::new);
+        predicate(modify);
+
+        // the components are looked up now, so that any that were never added fail here.
+        modify.resolve(&self.ecs.components)
     }
 
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.spawn.is_empty() &&
+        self.spawned == 0 &&
         self.destroy.is_empty() &&
-        self.modify.is_empty()
+        self.modify.is_empty() &&
+        self.clone.is_empty()
+    }
+
+    /// How many entities are spawned, destroyed or modified by these commands.
+    pub fn len(&self) -> usize {
+        self.spawn.iter().map(|(_, table)| table.len()).sum::<usize>() +
+        self.spawned +
+        self.destroy.values().map(|columns| columns.len()).sum::<usize>() +
+        self.modify.len() +
+        self.clone.len()
     }
 
-    pub fn submit(self) -> Trace<()> {
+    pub fn submit(mut self) -> Result<()> {
+        let len = self.len();
+        if let Some(counter) = self.counter.as_mut() {
+            **counter += len;
+        }
+
         if !self.is_empty() {
-            let ecs = self.ecs.get_mut();
-                unsafe {trace!((*ecs).archetypes.submit_commands(self)); }
+            let ecs = self.ecs.clone();
+            trace!(ecs.archetypes.submit_commands(self));
         }
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
@@ -118,9 +225,12 @@ impl Default for Commands {
     fn default() -> Self {
         Self { 
             ecs: Ptr::null(),
-            spawn: BTreeMap::new(),
+            spawn: Vec::new(),
+            spawned: 0,
             destroy: BTreeMap::new(),
             modify: BTreeMap::new(),
+            clone: Vec::new(),
+            counter: None,
         }
     }
 }
@@ -128,6 +238,9 @@ impl Default for Commands {
 pub struct Modify {
     pub(crate) insert: Vec<Anon>,
     pub(crate) remove: Vec<ComponentId>,
+    /// Typed components to remove, until their ids are looked up by `resolve`.
+    remove_types: Vec<TypeId>,
+    pub(crate) require: Vec<RequiredComponent>,
 }
 
 impl Modify {
@@ -135,32 +248,101 @@ impl Modify {
         Self {
             insert: Vec::with_capacity(8),
             remove: Vec::with_capacity(8),
+            remove_types: Vec::new(),
+            require: Vec::new(),
         }
     }
 
     pub(crate) fn extend(&mut self, modify: Modify) {
         self.insert.extend(modify.insert);
         self.remove.extend(modify.remove);
+        self.remove_types.extend(modify.remove_types);
+        self.require.extend(modify.require);
+    }
+
+    /// Sets the id every component has in the Ecs that owns `components`. Removing
+    /// a component that was never added is skipped, as no entity can have it.
+    pub(crate) fn resolve(&mut self, components: &Components) -> Result<()> {
+        for anon in self.insert.iter_mut() {
+            trace!(anon.resolve(components));
+        }
+
+        for req in self.require.iter_mut() {
+            trace!(components.resolve(&mut req.info));
+        }
+
+        let remove = self.remove_types.drain(..).filter_map(|type_id| components.id_of_type(type_id));
+        self.remove.extend(remove);
+
+        Ok(())
+    }
+
+    /// Inserts a component or [`Bundle`], replacing any components the entity already has.
+    /// Components it requires are added too, unless the entity already has them.
+    pub fn with<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        let mut infos = Vec::new();
+        B::infos(&mut infos);
+        self.require.extend(resolve_required(&mut infos));
+
+        let mut infos = infos.into_iter();
+        let insert = &mut self.insert;
+
+        bundle.take(&mut |ptr| {
+            let info = infos.next().unwrap();
+            insert.push(unsafe { Anon::from_raw(info, ptr) });
+        });
+
+        self
     }
 
-    pub fn with<C: Component>(&mut self, cmp: C) -> &mut Self {
-        self.insert.push(Anon::new::<C>(cmp));
+    /// Inserts a component that was built at runtime, replacing the one the entity
+    /// already has with the same id.
+    pub fn with_anon(&mut self, anon: Anon) -> &mut Self {
+        let mut infos = vec![*anon.info()];
+        self.require.extend(resolve_required(&mut infos));
+
+        self.insert.push(anon);
         self
     }
 
     pub fn without<C: Component>(&mut self) -> &mut Self {
-        self.remove.push(*C::handle());
+        self.remove_types.push(TypeId::of::<C>());
+        self
+    }
+
+    /// Removes the component with id `id`, if the entity has one.
+    pub fn without_id(&mut self, id: ComponentId) -> &mut Self {
+        self.remove.push(id);
+        self
+    }
+
+    /// Removes every component in the [`Bundle`] that the entity has.
+    pub fn without_bundle<B: Bundle>(&mut self) -> &mut Self {
+        let mut infos = Vec::new();
+        B::infos(&mut infos);
+
+        self.remove_types.extend(infos.iter().filter_map(|info| info.type_id));
         self
     }
 }
 
 impl Fetch for Commands {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok(Commands::new(ecs))
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        let mut commands = Commands::new(ecs);
+
+        if locals.profile {
+            commands.counter = Some(PtrMut::new(&mut locals.commands));
+        }
+
+        Ok(commands)
     }
 
-    fn access(_: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        Trace::Ok(())
+    fn access(_: &mut Vec<Accessor>, _: Ptr<Ecs>) -> Result<()> {
+        Ok(())
         // we dont access anything un-safely. 
     }
+}
+
+impl SystemParam for Commands {
+    type Item<'w> = Commands;
 }
\ No newline at end of file
@@ -0,0 +1,196 @@
+
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use super::anon::{Anon, ComponentInfo};
+use super::archetypes::ComponentId;
+use super::handle::Component;
+use super::reflect::{self, TypeInfo};
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
+
+/// How the components of a type are laid out in the world.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StorageType {
+    /// Stored in the table of the entity's archetype, next to the rest of its components.
+    Table,
+}
+
+/// Functions that are called on a component as it enters or leaves the world.
+///
+/// `on_add` runs once a component is spawned or inserted onto an entity, and `on_remove`
+/// runs right before the world drops it, whether it was destroyed, removed or replaced.
+/// Moving an entity between tables does not call either of them.
+#[derive(Copy, Clone, Default)]
+pub struct ComponentHooks {
+    pub on_add: Option<fn(*mut u8)>,
+    pub on_remove: Option<fn(*mut u8)>,
+}
+
+/// A component that is spawned with its default value whenever a component
+/// that requires it is spawned or inserted without it.
+#[derive(Copy, Clone)]
+pub struct RequiredComponent {
+    pub info: ComponentInfo,
+    pub default: fn() -> Anon,
+}
+
+impl RequiredComponent {
+    pub fn of<C: Component + Default>() -> Self {
+        Self {
+            info: ComponentInfo::of::<C>(),
+            default: || Anon::new(C::default()),
+        }
+    }
+}
+
+/// Clones the `C` behind `src` into `dst` with the cloner of `C`. 
+///
+/// # Safety
+/// `src` must point to a valid `C`, and `dst` to memory a `C` can be written to.
+/// `C` must have a cloner.
+pub(crate) unsafe fn clone_component<C: Component>(src: *const u8, dst: *mut u8) {
+    let clone = C::cloner().expect("only components with a cloner are cloned");
+    dst.cast::<C>().write(clone(&*src.cast::<C>()))
+}
+
+/// Adds the info of every required component that is missing from `infos`,
+/// including the requirements of requirements, and returns how to build them.
+pub(crate) fn resolve_required(infos: &mut Vec<ComponentInfo>) -> Vec<RequiredComponent> {
+    let mut missing = Vec::new();
+    let mut required = Vec::new();
+
+    let mut i = 0;
+    while i < infos.len() {
+        (infos[i].required)(&mut required);
+
+        for req in required.drain(..) {
+            if !infos.iter().any(|info| info.is(&req.info)) {
+                infos.push(req.info);
+                missing.push(req);
+            }
+        }
+
+        i += 1;
+    }
+
+    missing
+}
+
+/// Registry of every component that has been added to an Ecs.
+///
+/// Ids belong to the Ecs, so the same type can have a different id in every
+/// world it is added to. Typed components are looked up by their `TypeId`.
+pub struct Components {
+    infos: HashMap<ComponentId, ComponentInfo>,
+    names: HashMap<&'static str, ComponentId>,
+    types: HashMap<TypeId, ComponentId>,
+    next: ComponentId,
+}
+
+impl Components {
+    pub fn new() -> Self {
+        Self {
+            infos: HashMap::new(),
+            names: HashMap::new(),
+            types: HashMap::new(),
+            next: 0,
+        }
+    }
+
+    /// Gives `info` the next free id and registers it, or returns the id
+    /// its type already has. Fails once every id is in use, `u16::MAX` is
+    /// left for components that haven't been added.
+    pub(crate) fn register(&mut self, mut info: ComponentInfo) -> Result<ComponentId> {
+        if let Some(id) = info.type_id.and_then(|type_id| self.types.get(&type_id)) {
+            return Ok(*id);
+        }
+
+        // the fields of reflected components are found by their offsets, 
+        // so they have to be the fields of the component's own type.
+        if let Some(reflect) = info.reflect {
+            trace!(reflect::check(&info, &reflect()));
+        }
+
+        if self.next == u16::MAX {
+            start_trace!(EcsError::TooManyComponents(info.name));
+        }
+
+        info.id = self.next;
+        self.next += 1;
+
+        if let Some(type_id) = info.type_id {
+            self.types.insert(type_id, info.id);
+        }
+
+        self.names.insert(info.name, info.id);
+        self.infos.insert(info.id, info);
+
+        Ok(info.id)
+    }
+
+    /// The id of `C`, if it has been added.
+    pub fn id_of<C: Component>(&self) -> Option<ComponentId> {
+        self.id_of_type(TypeId::of::<C>())
+    }
+
+    pub fn id_of_type(&self, type_id: TypeId) -> Option<ComponentId> {
+        self.types.get(&type_id).copied()
+    }
+
+    /// The id of `C`, or an error if it has not been added.
+    pub(crate) fn lookup<C: Component>(&self) -> Result<ComponentId> {
+        match self.id_of::<C>() {
+            Some(id) => Ok(id),
+            None => start_trace!(EcsError::UnregisteredComponent(C::name())),
+        }
+    }
+
+    /// Sets the id `info` has in this registry. Typed components are found by type,
+    /// runtime-typed ones already carry the id they were added with.
+    pub(crate) fn resolve(&self, info: &mut ComponentInfo) -> Result<()> {
+        match info.type_id {
+            Some(type_id) => match self.types.get(&type_id) {
+                Some(id) => info.id = *id,
+                None => start_trace!(EcsError::UnregisteredComponent(info.name)),
+            },
+            None => if !self.infos.contains_key(&info.id) {
+                start_trace!(EcsError::UnknownComponent(info.id))
+            },
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve_all(&self, infos: &mut [ComponentInfo]) -> Result<()> {
+        for info in infos.iter_mut() {
+            trace!(self.resolve(info));
+        }
+
+        Ok(())
+    }
+
+    pub fn info(&self, id: ComponentId) -> Option<&ComponentInfo> {
+        self.infos.get(&id)
+    }
+
+    /// The fields of component `id`, if it is reflected.
+    pub fn type_info(&self, id: ComponentId) -> Option<TypeInfo> {
+        self.infos.get(&id)?.reflect.map(|reflect| reflect())
+    }
+
+    pub fn id(&self, name: &str) -> Option<ComponentId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.infos.values()
+    }
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self::new()
+    }
+}
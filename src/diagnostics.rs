@@ -0,0 +1,268 @@
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+
+use super::handle::Resource;
+use super::scheduler::SystemSample;
+
+/// Profiling data for the systems of an Ecs.
+///
+/// Systems are only timed while a `Diagnostics` resource has been added, see
+/// `Ecs::enable_diagnostics`. Every stage, group and system keeps the durations of
+/// its last `window` runs, and the last `window` frames are kept as trace events
+/// that can be opened in `chrome://tracing` or Perfetto with [`Diagnostics::to_chrome_trace`].
+pub struct Diagnostics {
+    window: usize,
+    epoch: Instant,
+    stages: IndexMap<&'static str, StageTimings>,
+    frames: VecDeque<Vec<TraceEvent>>,
+    current: Vec<TraceEvent>,
+    threads: Vec<ThreadId>,
+}
+
+/// The timings of a stage, and of the groups and systems in it.
+pub struct StageTimings {
+    pub timings: Timings,
+    pub groups: Vec<Timings>,
+    pub systems: IndexMap<&'static str, Timings>,
+}
+
+/// The durations of the last runs of a stage, group or system.
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    /// How long the last run took.
+    pub last: Duration,
+    /// Entities matched by the queries of a system during its last run.
+    /// Summed over the systems of a group or stage.
+    pub entities: usize,
+    /// Commands submitted by a system during its last run.
+    /// Summed over the systems of a group or stage.
+    pub commands: usize,
+    history: VecDeque<Duration>,
+}
+
+#[derive(Clone)]
+struct TraceEvent {
+    name: &'static str,
+    category: &'static str,
+    start: Instant,
+    duration: Duration,
+    thread: usize,
+}
+
+impl Timings {
+    /// The durations of the last runs, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// The mean duration over the window.
+    pub fn average(&self) -> Duration {
+        if self.history.is_empty() {
+            return Duration::ZERO;
+        }
+
+        self.history.iter().sum::<Duration>() / self.history.len() as u32
+    }
+
+    /// The longest duration in the window.
+    pub fn max(&self) -> Duration {
+        self.history.iter().copied().max().unwrap_or_default()
+    }
+
+    fn push(&mut self, window: usize, duration: Duration, entities: usize, commands: usize) {
+        self.last = duration;
+        self.entities = entities;
+        self.commands = commands;
+
+        self.history.push_back(duration);
+        while self.history.len() > window {
+            self.history.pop_front();
+        }
+    }
+}
+
+impl Diagnostics {
+    /// Keeps the last `window` runs of everything.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            epoch: Instant::now(),
+            stages: IndexMap::new(),
+            frames: VecDeque::new(),
+            current: Vec::new(),
+            threads: vec![thread::current().id()],
+        }
+    }
+
+    /// How many runs are kept.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// How many frames of trace events are kept, up to the window.
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Every stage that has run since diagnostics were enabled, in the order they first ran.
+    pub fn stages(&self) -> impl Iterator<Item = (&'static str, &StageTimings)> {
+        self.stages.iter().map(|(name, timings)| (*name, timings))
+    }
+
+    pub fn stage(&self, stage: &str) -> Option<&StageTimings> {
+        self.stages.get(stage)
+    }
+
+    /// The timings of the group at `index` in `stage`, numbered the same as in `ScheduleInfo`.
+    pub fn group(&self, stage: &str, index: usize) -> Option<&Timings> {
+        self.stages.get(stage)?.groups.get(index)
+    }
+
+    pub fn system(&self, stage: &str, system: &str) -> Option<&Timings> {
+        self.stages.get(stage)?.systems.get(system)
+    }
+
+    /// Forgets every timing and trace event.
+    pub fn clear(&mut self) {
+        self.stages.clear();
+        self.frames.clear();
+        self.current.clear();
+    }
+
+    /// Records a run of `stage` that started at `start` and took `duration`.
+    pub(crate) fn record(&mut self, stage: &'static str, start: Instant, duration: Duration, samples: Vec<SystemSample>) {
+        let window = self.window;
+        let main = self.thread(thread::current().id());
+
+        let timings = self.stages.entry(stage).or_insert_with(|| StageTimings {
+            timings: Timings::default(),
+            groups: Vec::new(),
+            systems: IndexMap::new(),
+        });
+
+        // A group runs from the start of its first system until its last one is done.
+        let mut groups: Vec<Option<(Instant, Instant, usize, usize)>> = Vec::new();
+        for sample in samples.iter() {
+            if groups.len() <= sample.group {
+                groups.resize(sample.group + 1, None);
+            }
+
+            let end = sample.start + sample.duration;
+            let span = groups[sample.group].get_or_insert((sample.start, end, 0, 0));
+            span.0 = span.0.min(sample.start);
+            span.1 = span.1.max(end);
+            span.2 += sample.entities;
+            span.3 += sample.commands;
+        }
+
+        if timings.groups.len() < groups.len() {
+            timings.groups.resize(groups.len(), Timings::default());
+        }
+
+        let mut events = vec![TraceEvent { name: stage, category: "stage", start, duration, thread: main }];
+
+        for (i, span) in groups.iter().enumerate() {
+            if let Some((first, last, entities, commands)) = *span {
+                timings.groups[i].push(window, last - first, entities, commands);
+                events.push(TraceEvent { name: "group", category: "group", start: first, duration: last - first, thread: main });
+            }
+        }
+
+        let (entities, commands) = samples.iter().fold((0, 0), |(e, c), sample| (e + sample.entities, c + sample.commands));
+        timings.timings.push(window, duration, entities, commands);
+
+        for sample in samples.iter() {
+            timings.systems
+                .entry(sample.name)
+                .or_default()
+                .push(window, sample.duration, sample.entities, sample.commands);
+        }
+
+        for sample in samples {
+            let thread = self.thread(sample.thread);
+            events.push(TraceEvent { name: sample.name, category: "system", start: sample.start, duration: sample.duration, thread });
+        }
+
+        self.current.append(&mut events);
+    }
+
+    /// Closes the events recorded since the last frame into a frame of their own.
+    pub(crate) fn end_frame(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+
+        self.frames.push_back(std::mem::take(&mut self.current));
+        while self.frames.len() > self.window {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Renders the kept frames as a Chrome trace-event JSON array. Stages and groups
+    /// are on the thread that ran the schedule, every system on the thread it ran on.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut json = String::from("[");
+
+        let events = self.frames.iter().flatten().chain(self.current.iter());
+        for (i, event) in events.enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+
+            // writing to a String can't fail.
+            let _ = write!(json,
+                "\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":{}}}",
+                escape(event.name),
+                event.category,
+                event.start.saturating_duration_since(self.epoch).as_micros(),
+                event.duration.as_micros(),
+                event.thread,
+            );
+        }
+
+        json.push_str("\n]");
+        json
+    }
+
+    /// A small number for `thread` that stays the same for as long as diagnostics are kept.
+    fn thread(&mut self, thread: ThreadId) -> usize {
+        match self.threads.iter().position(|id| *id == thread) {
+            Some(index) => index,
+            None => {
+                self.threads.push(thread);
+                self.threads.len() - 1
+            },
+        }
+    }
+}
+
+impl Resource for Diagnostics {}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new(60)
+    }
+}
+
+/// Escapes `text` to go between double quotes, in the JSON traces here and the DOT
+/// graphs of [`ScheduleInfo`](super::introspect::ScheduleInfo). Control characters
+/// become `\uXXXX`, so a name can't break out of its line either.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => { let _ = write!(escaped, "\\u{:04x}", c as u32); },
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
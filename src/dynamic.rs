@@ -0,0 +1,342 @@
+
+use std::any::{type_name, TypeId};
+use std::marker::PhantomData;
+
+use super::archetypes::{Archetype, Column, ComponentId, TableIndex};
+use super::package::PackageIndex;
+use super::params::{Fetch, Locals, SystemParam};
+use super::scheduler::Accessor;
+use super::handle::Handle;
+use super::table::Table;
+use super::ecs::Ecs;
+use super::ptr::Ptr;
+use super::error::{EcsError, Result};
+use super::start_trace;
+
+/// How a [`DynamicQuery`] uses one of its components.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    /// Matches entities with the component, and hands out a pointer to read it.
+    Read,
+    /// Matches entities with the component, and hands out a pointer to write it.
+    Write,
+    /// Hands out a pointer to read the component if the entity has one.
+    OptionalRead,
+    /// Hands out a pointer to write the component if the entity has one.
+    OptionalWrite,
+    /// Only matches entities with the component, without handing it out.
+    With,
+    /// Only matches entities without the component.
+    Without,
+}
+
+impl Term {
+    /// Whether rows have a pointer for the component.
+    fn fetches(self) -> bool {
+        !matches!(self, Term::With | Term::Without)
+    }
+
+    /// Whether entities have to have the component to match.
+    fn required(self) -> bool {
+        matches!(self, Term::Read | Term::Write | Term::With)
+    }
+
+    fn writes(self) -> bool {
+        matches!(self, Term::Write | Term::OptionalWrite)
+    }
+}
+
+/// Builds a query out of component ids that are only known at runtime,
+/// such as the components a script defined with `Ecs::add_dynamic_component`.
+///
+/// The pointers of every row come in the order the fetched components were added.
+pub struct QueryBuilder {
+    terms: Vec<(ComponentId, Term)>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        Self {
+            terms: Vec::new(),
+        }
+    }
+
+    pub fn term(mut self, id: ComponentId, term: Term) -> Self {
+        self.terms.push((id, term));
+        self
+    }
+
+    pub fn read(self, id: ComponentId) -> Self {
+        self.term(id, Term::Read)
+    }
+
+    pub fn write(self, id: ComponentId) -> Self {
+        self.term(id, Term::Write)
+    }
+
+    pub fn read_optional(self, id: ComponentId) -> Self {
+        self.term(id, Term::OptionalRead)
+    }
+
+    pub fn write_optional(self, id: ComponentId) -> Self {
+        self.term(id, Term::OptionalWrite)
+    }
+
+    /// Matches entities that have component `id`, without handing it out.
+    pub fn with(self, id: ComponentId) -> Self {
+        self.term(id, Term::With)
+    }
+
+    pub fn without(self, id: ComponentId) -> Self {
+        self.term(id, Term::Without)
+    }
+
+    /// Checks that every component has been added to `ecs` and appears only once.
+    ///
+    /// The query finds its tables in the query cache once it is registered there,
+    /// by [`Ecs::add_dynamic_query`] or by a system that fetches it as a [`Dynamic`],
+    /// and checks every table until then.
+    pub fn build(self, ecs: &Ecs) -> Result<DynamicQuery> {
+        for (i, (id, _)) in self.terms.iter().enumerate() {
+            let Some(info) = ecs.components().info(*id) else {
+                start_trace!(EcsError::UnknownComponent(*id))
+            };
+
+            if self.terms[..i].iter().any(|(other, _)| other == id) {
+                start_trace!(EcsError::DuplicateComponent(info.name));
+            }
+        }
+
+        let required: Vec<ComponentId> = self.terms.iter()
+            .filter(|(_, term)| term.required())
+            .map(|(id, _)| *id)
+            .collect();
+
+        // the same key a static query for the required components would have,
+        // so both share one entry in the cache.
+        let key = Archetype::of(&required);
+
+        Ok(DynamicQuery { key, required, terms: self.terms })
+    }
+}
+
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A query built by a [`QueryBuilder`] for one Ecs.
+#[derive(Clone)]
+pub struct DynamicQuery {
+    key: Archetype,
+    required: Vec<ComponentId>,
+    terms: Vec<(ComponentId, Term)>,
+}
+
+impl DynamicQuery {
+    pub fn terms(&self) -> &[(ComponentId, Term)] {
+        &self.terms
+    }
+
+    /// Whether any component is fetched with [`Term::Write`] or [`Term::OptionalWrite`].
+    pub fn writes(&self) -> bool {
+        self.terms.iter().any(|(_, term)| term.writes())
+    }
+
+    /// Iterates over every matched entity. Fails if the query writes to any
+    /// component, use [`iter_mut`](Self::iter_mut) for those.
+    pub fn iter<'w>(&self, ecs: &'w Ecs) -> Result<DynamicIter<'w>> {
+        if self.writes() {
+            start_trace!(EcsError::MutableQuery);
+        }
+
+        self.matches(ecs)
+    }
+
+    /// Iterates over every matched entity, with the Ecs borrowed mutably
+    /// so that the components can be written to.
+    pub fn iter_mut<'w>(&self, ecs: &'w mut Ecs) -> Result<DynamicIter<'w>> {
+        self.matches(ecs)
+    }
+
+    /// Registers the query with the query cache of `ecs`.
+    pub(crate) fn register(&self, ecs: &mut Ecs) -> Result<()> {
+        ecs.archetypes.load_query(self.key, self.required.clone())
+    }
+
+    /// The accessors a system that fetches the query declares.
+    fn accessors(&self, v: &mut Vec<Accessor>) {
+        for (id, term) in self.terms.iter() {
+            match term {
+                Term::Read | Term::OptionalRead => v.push(Accessor::Ref(*id)),
+                Term::Write | Term::OptionalWrite => v.push(Accessor::Mut(*id)),
+                Term::With | Term::Without => {},
+            }
+        }
+    }
+
+    fn matches<'w>(&self, ecs: &'w Ecs) -> Result<DynamicIter<'w>> {
+        let tables = ecs.archetypes.tables();
+        let indices: Vec<TableIndex> = match ecs.archetypes.query_cache(self.key) {
+            Ok(indices) => indices.iter().copied().collect(),
+            Err(_) => (0..tables.len()).filter(|index| tables[*index].contains(&self.required)).collect(),
+        };
+
+        let mut matched = Vec::with_capacity(indices.len());
+        for index in indices {
+            let table = &tables[index];
+
+            if table.is_empty() || self.excludes(table) {
+                continue;
+            }
+
+            let rows = self.terms.iter()
+                .filter(|(_, term)| term.fetches())
+                .map(|(id, _)| table.row(*id).map(|row| (row.as_ptr(), row.info().layout.size())))
+                .collect();
+
+            matched.push(MatchedTable { index, len: table.len(), rows });
+        }
+
+        Ok(DynamicIter {
+            tables: matched,
+            table: 0,
+            col: 0,
+            marker: PhantomData,
+        })
+    }
+
+    /// Whether `table` has a component the query filters out.
+    fn excludes(&self, table: &Table) -> bool {
+        self.terms.iter().any(|(id, term)| *term == Term::Without && table.row(*id).is_some())
+    }
+}
+
+/// The [`DynamicQuery`] added with `Ecs::add_dynamic_query::<L>`, as a system parameter.
+///
+/// The label `L` lets the scheduler find the query's components when it plans the
+/// stage, so systems that use dynamic queries are ordered like any other.
+pub struct Dynamic<'w, L> {
+    ecs: Ptr<Ecs>,
+    marker: PhantomData<(&'w Ecs, L)>,
+}
+
+impl<L: Handle> Dynamic<'_, L> {
+    pub fn query(&self) -> &DynamicQuery {
+        // fetching checked that the query was added, and it can't be replaced.
+        &self.ecs.queries[&TypeId::of::<L>()]
+    }
+
+    /// Iterates over every matched entity. Fails if the query writes to any
+    /// component, use [`iter_mut`](Self::iter_mut) for those.
+    pub fn iter(&self) -> Result<DynamicIter<'_>> {
+        if self.query().writes() {
+            start_trace!(EcsError::MutableQuery);
+        }
+
+        // the rows borrow self, and self only lives as long as the system run.
+        self.query().matches(unsafe { self.ecs.as_ref() })
+    }
+
+    /// Iterates over every matched entity, so that the components fetched
+    /// to be written can be written to.
+    pub fn iter_mut(&mut self) -> Result<DynamicIter<'_>> {
+        self.query().matches(unsafe { self.ecs.as_ref() })
+    }
+}
+
+impl<L> Default for Dynamic<'_, L> {
+    fn default() -> Self {
+        Self { ecs: Ptr::null(), marker: PhantomData }
+    }
+}
+
+impl<L: Handle> Fetch for Dynamic<'_, L> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        let Some(query) = ecs.queries.get(&TypeId::of::<L>()) else {
+            start_trace!(EcsError::MissingDynamicQuery(type_name::<L>()))
+        };
+
+        if locals.profile {
+            locals.entities += ecs.archetypes.count(query.key);
+        }
+
+        Ok(Self { ecs: ecs.clone(), marker: PhantomData })
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let Some(query) = ecs.queries.get(&TypeId::of::<L>()) else {
+            start_trace!(EcsError::MissingDynamicQuery(type_name::<L>()))
+        };
+
+        query.accessors(v);
+
+        // stages are planned while no system is running, like static queries register.
+        unsafe { query.register(&mut *ecs.get_mut()) }
+    }
+}
+
+impl<L: Handle> SystemParam for Dynamic<'static, L> {
+    type Item<'w> = Dynamic<'w, L>;
+}
+
+/// The entities matched by a [`DynamicQuery`], borrowed from the Ecs for `'w`.
+pub struct DynamicIter<'w> {
+    tables: Vec<MatchedTable>,
+    table: usize,
+    col: Column,
+    marker: PhantomData<&'w Ecs>,
+}
+
+/// A table matched by a query, with the start and element size of each fetched
+/// row, or `None` for optional components the table doesn't have.
+struct MatchedTable {
+    index: TableIndex,
+    len: usize,
+    rows: Vec<Option<(*mut u8, usize)>>,
+}
+
+impl Iterator for DynamicIter<'_> {
+    type Item = DynamicRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let table = self.tables.get(self.table)?;
+
+        let col = self.col;
+        let ptrs = table.rows.iter()
+            .map(|row| row.map(|(start, size)| unsafe { start.add(size * col) }))
+            .collect();
+
+        self.col += 1;
+        if self.col == table.len {
+            self.table += 1;
+            self.col = 0;
+        }
+
+        Some(DynamicRow {
+            entity: PackageIndex { table: table.index, col },
+            ptrs,
+        })
+    }
+}
+
+/// One entity matched by a [`DynamicQuery`], with a pointer to each fetched component.
+pub struct DynamicRow {
+    pub entity: PackageIndex,
+    ptrs: Vec<Option<*mut u8>>,
+}
+
+impl DynamicRow {
+    /// The fetched component at position `i`, or `None` if it is optional and the
+    /// entity doesn't have it. Reading or writing through it is only sound while
+    /// the iterator's borrow of the Ecs lasts, and writing only if it was fetched
+    /// to be written.
+    pub fn get(&self, i: usize) -> Option<*mut u8> {
+        self.ptrs.get(i).copied().flatten()
+    }
+
+    pub fn ptrs(&self) -> &[Option<*mut u8>] {
+        &self.ptrs
+    }
+}
@@ -1,29 +1,59 @@
 
-use std::sync::Arc;
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use super::resources::Resources;
-use super::params::{System, Fetch, ResMut, ResRef};
+use super::params::{IntoSystem, NonSend, NonSendMut, ResMut, ResRef};
 use super::handle::Resource;
-use super::error::Trace;
+use super::error::{EcsError, Result};
 use super::systems::Systems;
+use super::scheduler::{Accessor, AmbiguityCheck, ErrorHandler, Executor, OnPanic};
+use super::introspect::ScheduleInfo;
+use super::diagnostics::Diagnostics;
 use super::archetypes::Archetypes;
 use super::handle::Handle;
 use super::handle::Component;
 use super::ptr::Ptr;
+use super::bundle::{Bundle, DynamicBundle};
+use super::anon::ComponentInfo;
+use super::components::Components;
+use super::package::{Package, PackageIndex, Reserved};
+use super::prefab::{Prefab, PrefabInstance};
+use super::dynamic::{DynamicQuery, QueryBuilder};
+use super::archetypes::ComponentId;
+use super::reflect::{self, Reflect};
+use super::{start_trace, trace};
 
 pub struct Ecs {
     pub(crate) archetypes: Archetypes,
     pub(crate) resources: Resources,
     pub(crate) systems: Systems,
+    pub(crate) components: Components,
+    /// The queries systems fetch as [`Dynamic`] parameters, by label.
+    pub(crate) queries: HashMap<TypeId, DynamicQuery>,
+    pub(crate) change_tick: AtomicU64,
 }
 
 impl Ecs {
-    pub fn execute_startup(&mut self) {
-        self.systems.execute_startup(Ptr::new(self));
+    /// Finalizes every schedule and runs the [`Startup`] schedule.
+    pub fn execute_startup(&mut self) -> Result<()> {
+        Systems::execute_startup(Ptr::new(self))
     }
 
-    pub fn execute_systems(&self) {
-        self.systems.execute_systems(Ptr::new(self));
+    /// Runs the [`Update`] schedule.
+    pub fn execute_systems(&mut self) -> Result<()> {
+        Systems::execute_systems(Ptr::new(self))
+    }
+
+    /// Runs the schedule labelled `L`. Can be called from an exclusive system
+    /// to run one schedule in the middle of another, but not the one it is in.
+    pub fn run_schedule<L: Handle>(&mut self) -> Result<()> {
+        let Some(index) = self.systems.schedule::<L>() else {
+            start_trace!(EcsError::ScheduleNotFound(type_name::<L>()))
+        };
+
+        Systems::run_schedule(Ptr::new(self), index, type_name::<L>())
     }
 
     pub fn new() -> Self {
@@ -31,43 +61,318 @@ impl Ecs {
             archetypes: Archetypes::new(),
             resources: Resources::new(),
             systems: Systems::new(),
+            components: Components::new(),
+            queries: HashMap::new(),
+            change_tick: AtomicU64::new(0),
         }
     }
 
-    pub fn get_resource_mut<R: Resource>(&self) -> Trace<ResMut<R>> {
-        self.resources.get_resource_mut::<R>()
+    /// The resource, outside of any system. Every resource counts as changed 
+    /// to it, and changes made through it are seen by every system.
+    pub fn get_resource_mut<R: Resource>(&mut self) -> Result<ResMut<'_, R>> {
+        self.resources.get_resource_mut::<R>(0, self.next_tick())
+    }
+
+    /// The resource, outside of any system. Every resource counts as changed to it.
+    pub fn get_resource_ref<R: Resource>(&self) -> Result<ResRef<'_, R>> {
+        self.resources.get_resource_ref::<R>(0)
+    }
+
+    /// The change tick of the most recent system run or change made outside of systems.
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick.load(Ordering::Acquire)
     }
 
-    pub fn get_resource_ref<R: Resource>(&self) -> Trace<ResRef<R>> {
-        self.resources.get_resource_ref::<R>()
+    /// Starts a new change tick, for a system run or a change made outside of systems.
+    pub(crate) fn next_tick(&self) -> u64 {
+        self.change_tick.fetch_add(1, Ordering::AcqRel) + 1
     } 
 
-    pub fn add_component<C: Component>(&mut self) {
-        static mut COUNTER: u16 = 0;
+    /// Adds the component `C`, returning its id in this Ecs. Adding it again returns the same id.
+    pub fn add_component<C: Component>(&mut self) -> Result<ComponentId> {
+        self.components.register(ComponentInfo::of::<C>())
+    }
+
+    /// Adds a component that has no Rust type, described by `info`, and returns its id.
+    /// Its values are spawned and inserted as [`Anon`](super::anon::Anon)s built with
+    /// `Anon::from_raw`, and read through [`get_raw`](Self::get_raw) or a [`QueryBuilder`](super::dynamic::QueryBuilder).
+    ///
+    /// The name has to be unique, as scripts look components up by it.
+    pub fn add_dynamic_component(&mut self, info: ComponentInfo) -> Result<ComponentId> {
+        if self.components.id(info.name).is_some() {
+            start_trace!(EcsError::DuplicateComponentName(info.name));
+        }
+
+        self.components.register(info)
+    }
+
+    /// Builds the query that systems fetch as a [`Dynamic<L>`](super::dynamic::Dynamic),
+    /// and registers it with the query cache. A label only ever has one query,
+    /// since the systems that use it were planned around its components.
+    pub fn add_dynamic_query<L: Handle>(&mut self, builder: QueryBuilder) -> Result<()> {
+        if self.queries.contains_key(&TypeId::of::<L>()) {
+            start_trace!(EcsError::DuplicateDynamicQuery(type_name::<L>()));
+        }
+
+        let query = trace!(builder.build(self));
+        trace!(query.register(self));
+        self.queries.insert(TypeId::of::<L>(), query);
+
+        Ok(())
+    }
+
+    /// Every component that has been added to the world, along 
+    /// with its layout, hooks and other per-type metadata. 
+    pub fn components(&self) -> &Components {
+        &self.components
+    }
+
+    /// Spawns an entity from a [`Bundle`] or [`Package`](super::package::Package)
+    /// straight into the table for its archetype. Returns the index of the new entity.
+    pub fn spawn<B: DynamicBundle>(&mut self, bundle: B) -> Result<PackageIndex> {
+        trace!(self.flush());
+        self.archetypes.spawn(bundle, &self.components)
+    }
+
+    /// Spawns an instance of `prefab` and every one of its children.
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) -> Result<PrefabInstance> {
+        self.spawn_prefab_with(prefab, Package::new())
+    }
+
+    /// Spawns an instance of `prefab` whose root has the components of `overrides`
+    /// in place of the prefab's own, and every one of its children.
+    pub fn spawn_prefab_with<B: DynamicBundle>(&mut self, prefab: &Prefab, overrides: B) -> Result<PrefabInstance> {
+        let root = trace!(self.spawn(trace!(prefab.instantiate(overrides))));
+
+        let mut children = Vec::with_capacity(prefab.children().len());
+        for child in prefab.children() {
+            children.push(trace!(self.spawn_prefab(child)));
+        }
+
+        Ok(PrefabInstance { root, children })
+    }
+
+    /// Reads a [`Prefab`] from RON text, with the components that have been added to the world.
+    #[cfg(feature = "serialize")]
+    pub fn load_prefab(&self, text: &str) -> Result<Prefab> {
+        Prefab::from_ron(text, &self.components)
+    }
+
+    /// Spawns a copy of `entity` with a clone of every one of its components,
+    /// returning the index of the copy. Fails if any of them can't be cloned.
+    pub fn clone_entity(&mut self, entity: PackageIndex) -> Result<PackageIndex> {
+        trace!(self.flush());
+        self.archetypes.clone_entity(entity, &self.components)
+    }
+
+    /// A pointer to component `id` of `entity`, or `None` if the entity doesn't have one.
+    /// Fails if `entity` doesn't exist.
+    pub fn get_raw(&self, entity: PackageIndex, id: ComponentId) -> Result<Option<*mut u8>> {
+        self.archetypes.get_raw(entity, id)
+    }
+
+    /// The field at `path` of component `id` of `entity`, which has to be reflected.
+    pub fn field<T: Reflect>(&self, entity: PackageIndex, id: ComponentId, path: &str) -> Result<&T> {
+        unsafe { Ok(&*trace!(self.field_ptr::<T>(entity, id, path))) }
+    }
+
+    pub fn field_mut<T: Reflect>(&mut self, entity: PackageIndex, id: ComponentId, path: &str) -> Result<&mut T> {
+        unsafe { Ok(&mut *trace!(self.field_ptr::<T>(entity, id, path))) }
+    }
 
-        unsafe { 
-            *C::handle() = COUNTER; 
-            COUNTER += 1;
+    fn field_ptr<T: Reflect>(&self, entity: PackageIndex, id: ComponentId, path: &str) -> Result<*mut T> {
+        let Some(info) = self.components.info(id) else {
+            start_trace!(EcsError::UnknownComponent(id))
+        };
+
+        let Some(ptr) = trace!(self.get_raw(entity, id)) else {
+            start_trace!(EcsError::ComponentMismatch(info.name))
+        };
+
+        unsafe { reflect::field_ptr::<T>(ptr, info, path) }
+    }
+
+    /// Spawns an entity for every bundle in `iter`, writing them straight into
+    /// the table for their archetype. Returns the index of each new entity. 
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, iter: I) -> Result<Vec<PackageIndex>> {
+        trace!(self.flush());
+        self.archetypes.spawn_batch(iter, &self.components)
+    }
+
+    /// Where an entity reserved by [`Commands`](super::commands::Commands) was spawned,
+    /// from the flush that spawns it until the next one.
+    pub fn resolve(&self, reserved: Reserved) -> Result<PackageIndex> {
+        self.archetypes.resolve(reserved)
+    }
+
+    /// Applies the commands that systems have submitted, and spawns the entities they reserved.
+    /// This happens after every stage, and before anything is spawned outside of commands,
+    /// so that what was reserved is spawned before anything else changes.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.archetypes.has_queued() {
+            return Ok(());
         }
+
+        self.archetypes.flush_queues(&self.components)
+    }
+
+    /// Releases memory held by the world that is no longer in use,
+    /// such as table capacity left behind by destroyed entities.
+    pub fn compact(&mut self) {
+        self.archetypes.compact();
     }
 
     pub fn add_resource<R: Resource>(&mut self, resource: R) {
-        self.resources.add_resource(resource)
+        self.resources.add_resource(resource, self.next_tick())
+    }
+
+    /// Inserts `resource`, returning the one it replaced if there was one.
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> Option<R> {
+        let tick = self.next_tick();
+        self.resources.insert_resource(resource, tick)
+    }
+
+    /// Inserts the default value of `R`, unless there already is one.
+    pub fn init_resource<R: Resource + Default>(&mut self) {
+        let tick = self.next_tick();
+        self.resources.init_resource::<R>(tick)
+    }
+
+    /// Removes the resource and hands it back. Systems that take it as an `Option`
+    /// see `None` from then on, and the others fail until it is inserted again.
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.resources.remove_resource::<R>()
+    }
+
+    pub fn contains_resource<R: Resource>(&self) -> bool {
+        self.resources.contains_resource::<R>()
+    }
+
+    /// Inserts a resource that isn't `Send`, such as a window handle. It can only be used
+    /// on this thread, and systems that take it always run on the thread that runs their schedule.
+    /// Returns the one it replaced, and fails if that one belongs to another thread.
+    pub fn insert_non_send<R: 'static>(&mut self, resource: R) -> Result<Option<R>> {
+        let tick = self.next_tick();
+        self.resources.insert_non_send(resource, tick)
+    }
+
+    /// Removes the non-send resource and hands it back. Fails on any thread but the one it was inserted on.
+    pub fn remove_non_send<R: 'static>(&mut self) -> Result<Option<R>> {
+        self.resources.remove_non_send::<R>()
+    }
+
+    pub fn contains_non_send<R: 'static>(&self) -> bool {
+        self.resources.contains_non_send::<R>()
+    }
+
+    /// The non-send resource, outside of any system. Fails on any thread but the one it was inserted on.
+    pub fn get_non_send_ref<R: 'static>(&self) -> Result<NonSend<'_, R>> {
+        self.resources.get_non_send_ref::<R>(0)
+    }
+
+    /// The non-send resource, outside of any system. Fails on any thread but the one it was inserted on.
+    pub fn get_non_send_mut<R: 'static>(&mut self) -> Result<NonSendMut<'_, R>> {
+        self.resources.get_non_send_mut::<R>(0, self.next_tick())
+    }
+
+    pub fn add_startup<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.systems.add_startup::<H, M>(system)
+    }
+
+    pub fn add_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.systems.add_system::<H, M>(system)
+    }
+
+    /// Removes `system` from the stage `H`, which is planned again before it next runs.
+    pub fn remove_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.systems.remove_system::<H, M>(system)
+    }
+
+    /// Runs `system` in the stage `H` again after it was disabled.
+    pub fn enable_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.systems.set_system_enabled::<H, M>(system, true)
     }
 
-    pub fn add_startup<S: System + Fetch, H: Handle>(&mut self) {
-        self.systems.add_startup::<S, H>();
+    /// Skips `system` in the stage `H` until it is enabled again.
+    pub fn disable_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.systems.set_system_enabled::<H, M>(system, false)
     }
 
-    pub fn add_system<S: System + Fetch, H: Handle>(&mut self) {
-        self.systems.add_system::<S, H>();
+    pub fn is_system_enabled<H: Handle, M>(&self, system: impl IntoSystem<M>) -> Result<bool> {
+        self.systems.is_system_enabled::<H, M>(system)
     }
 
-    pub fn add_startup_stage<H: Handle>(&mut self) {
-        self.systems.add_startup_stage::<H>();
+    /// Removes the stage `H` and every system in it.
+    pub fn remove_stage<H: Handle>(&mut self) -> Result<()> {
+        self.systems.remove_stage::<H>()
     }
 
-    pub fn add_system_stage<H: Handle>(&mut self) {
-        self.systems.add_systems_stage::<H>();
+    /// Adds an empty schedule labelled `L`, next to [`Startup`] and [`Update`].
+    pub fn add_schedule<L: Handle>(&mut self) -> Result<()> {
+        self.systems.add_schedule::<L>()
     }
-}
\ No newline at end of file
+
+    /// Adds the stage `H` to the end of the schedule labelled `L`.
+    pub fn add_stage<L: Handle, H: Handle>(&mut self) -> Result<()> {
+        self.systems.add_stage::<L, H>()
+    }
+
+    pub fn add_startup_stage<H: Handle>(&mut self) -> Result<()> {
+        self.systems.add_startup_stage::<H>()
+    }
+
+    pub fn add_system_stage<H: Handle>(&mut self) -> Result<()> {
+        self.systems.add_systems_stage::<H>()
+    }
+
+    /// Sets the function that is called for every system that returns an error,
+    /// instead of printing it. 
+    pub fn set_error_handler(&mut self, on_error: ErrorHandler) {
+        self.systems.set_error_handler(on_error);
+    }
+
+    /// Sets how the systems of every stage are run: in parallel groups, dispatched 
+    /// as soon as they can run, or one at a time on the calling thread.
+    pub fn set_executor(&mut self, executor: Executor) {
+        self.systems.set_executor(executor);
+    }
+
+    /// Sets whether conflicting systems with no order between them are ignored, 
+    /// printed, or make `execute_startup` fail. 
+    pub fn set_ambiguity_check(&mut self, check: AmbiguityCheck) {
+        self.systems.set_ambiguity_check(check);
+    }
+
+    /// Starts timing every stage, group and system, keeping the last `window` runs
+    /// in a [`Diagnostics`] resource. 
+    pub fn enable_diagnostics(&mut self, window: usize) {
+        self.add_resource(Diagnostics::new(window));
+    }
+
+    /// Describes every schedule and stage, how its systems are grouped, and which
+    /// accesses kept systems from running in parallel.
+    pub fn schedule_info(&self) -> ScheduleInfo {
+        self.systems.info(&|accessor| self.accessor_name(accessor))
+    }
+
+    /// The type name of the component or resource behind `accessor`.
+    pub(crate) fn accessor_name(&self, accessor: Accessor) -> &'static str {
+        match accessor {
+            Accessor::Ref(id) | Accessor::Mut(id) => self.components.info(id).map_or("unknown", |info| info.name),
+            Accessor::ResRef(id) | Accessor::ResMut(id) => self.resources.name(id).unwrap_or("unknown"),
+            Accessor::MainThread => "main thread",
+        }
+    }
+
+    /// Sets whether a panicking system brings down the process, or is caught,
+    /// reported to the error handler, and optionally disabled.
+    pub fn set_panic_policy(&mut self, on_panic: OnPanic) {
+        self.systems.set_panic_policy(on_panic);
+    }
+}
+
+impl Default for Ecs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
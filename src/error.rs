@@ -1,40 +1,177 @@
 
+use thiserror::Error;
 
-pub enum Trace<T> {
-    Ok(T),
-    Err(String)
+use super::archetypes::{Column, ComponentId, TableIndex};
+
+#[doc(hidden)]
+pub use stdext;
+
+pub type Result<T, E = EcsError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while using the Ecs.
+///
+/// Errors created with `start_trace!` and passed up with `trace!` are wrapped in
+/// [`EcsError::Context`], once for every function they pass through. Use
+/// [`EcsError::kind`] to get at the error underneath.
+#[derive(Debug, Error)]
+pub enum EcsError {
+    #[error("component {0} has not been added to the Ecs")]
+    UnregisteredComponent(&'static str),
+
+    #[error("component {0} appears more than once in the same bundle")]
+    DuplicateComponent(&'static str),
+
+    #[error("a component named {0} has already been added")]
+    DuplicateComponentName(&'static str),
+
+    #[error("there is no component with id {0}")]
+    UnknownComponent(ComponentId),
+
+    #[error("every component id is in use, {0} can't be added")]
+    TooManyComponents(&'static str),
+
+    #[error("component {0} can't be cloned")]
+    NotCloneable(&'static str),
+
+    #[error("component {0} can't be serialized")]
+    NotSerializable(&'static str),
+
+    #[error("component {0} couldn't be serialized: {1}")]
+    SerializeFailed(&'static str, String),
+
+    #[error("there is no component named {0}")]
+    UnknownComponentName(String),
+
+    #[error("invalid prefab: {0}")]
+    InvalidPrefab(String),
+
+    #[error("component {0} is not reflected")]
+    NotReflected(&'static str),
+
+    #[error("component {component} is reflected as {reflected}")]
+    ReflectMismatch { component: &'static str, reflected: &'static str },
+
+    #[error("component {component} has no field at {path}")]
+    FieldNotFound { component: &'static str, path: String },
+
+    #[error("field {path} is a {found}, not a {expected}")]
+    FieldMismatch { path: String, expected: &'static str, found: &'static str },
+
+    #[error("component {0} does not belong in this table")]
+    ComponentMismatch(&'static str),
+
+    #[error("resource {0} has not been added to the Ecs")]
+    MissingResource(&'static str),
+
+    #[error("non-send resource {0} can only be used on the thread it was inserted on")]
+    WrongThread(&'static str),
+
+    #[error("the entity has not been spawned yet, or was spawned before the last flush")]
+    UnresolvedSpawn,
+
+    #[error("there is no entity in column {col} of table {table}")]
+    InvalidEntity { table: TableIndex, col: Column },
+
+    #[error("index {index} is out of bounds for a column of length {len}")]
+    IndexOutOfBounds { index: usize, len: usize },
+
+    #[error("stage {0} has not been added")]
+    StageNotFound(&'static str),
+
+    #[error("stage {0} has already been added")]
+    DuplicateStage(&'static str),
+
+    #[error("system {system} is not in stage {stage}")]
+    SystemNotFound { stage: &'static str, system: &'static str },
+
+    #[error("system {system} is already in stage {stage}, closures defined in the same function share a name")]
+    DuplicateSystem { stage: &'static str, system: &'static str },
+
+    #[error("schedule {0} has not been added")]
+    ScheduleNotFound(&'static str),
+
+    #[error("schedule {0} has already been added")]
+    DuplicateSchedule(&'static str),
+
+    #[error("schedule {0} can't be changed or run again while it is running")]
+    ScheduleRunning(&'static str),
+
+    #[error("the query was used before it was registered with the query cache")]
+    QueryNotRegistered,
+
+    #[error("the query writes to components, so it needs mutable access to the Ecs")]
+    MutableQuery,
+
+    #[error("no dynamic query has been added for {0}")]
+    MissingDynamicQuery(&'static str),
+
+    #[error("a dynamic query has already been added for {0}")]
+    DuplicateDynamicQuery(&'static str),
+
+    #[error("system {system} accesses {a} and {b}, which would alias each other")]
+    AccessConflict { system: &'static str, a: String, b: String },
+
+    #[error("systems {a} and {b} in stage {stage} conflict, but neither is ordered before the other")]
+    Ambiguous { stage: &'static str, a: &'static str, b: &'static str },
+
+    #[error("system {0} is ordered to run before itself")]
+    OrderingCycle(&'static str),
+
+    #[error("panicked: {0}")]
+    Panicked(String),
+
+    #[error("internal error, contact maintainer: {0}")]
+    Internal(&'static str),
+
+    /// An error returned by a system.
+    #[error(transparent)]
+    System(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("{error}\n  ...in file {file} in function {function} at line {line}")]
+    Context {
+        error: Box<EcsError>,
+        file: &'static str,
+        function: &'static str,
+        line: u32,
+    },
 }
 
-impl<T> Trace<T> {
-    pub fn unwrap(self) -> T {
+impl EcsError {
+    /// Records where the error passed through.
+    pub fn context(self, file: &'static str, function: &'static str, line: u32) -> Self {
+        Self::Context { error: Box::new(self), file, function, line }
+    }
+
+    /// The error underneath any context.
+    pub fn kind(&self) -> &EcsError {
         match self {
-            Trace::Ok(v) => v,
-            Trace::Err(string) => {
-                panic!("Panicked on a Trace unwrap value with error: {}", string)
-            },
+            Self::Context { error, .. } => error.kind(),
+            error => error,
         }
     }
 }
 
 pub mod macros {
+    /// Returns the error from the current function, tagged with where it happened.
     #[macro_export]
     macro_rules! start_trace {
-        ($message:expr) => {
-            return Trace::Err(format!("Error in file {} in function {} at line {} with message: 
-            {}", file!(), stdext::function_name!(), line!(), $message))
+        ($error:expr) => {
+            return Err($crate::EcsError::from($error)
+                .context(file!(), $crate::stdext::function_name!(), line!()))
         };
     }
 
+    /// Unwraps a result, or returns its error tagged with where it passed through.
     #[macro_export]
     macro_rules! trace {
         ($expression: expr) => {
             match $expression {
-                Trace::Ok(v) => v,
-                Trace::Err(msg) => {
-                    return Trace::Err(msg + &format!("\n  ...in file {} in function {} at line {},", 
-                    file!(), stdext::function_name!(), line!()))
+                Ok(v) => v,
+                Err(err) => {
+                    return Err($crate::EcsError::from(err)
+                        .context(file!(), $crate::stdext::function_name!(), line!()))
                 },
             }
         };
     }
-}
\ No newline at end of file
+}
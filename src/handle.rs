@@ -1,21 +1,60 @@
 
 use std::any::{Any, type_name};
 
+use super::components::{StorageType, ComponentHooks, RequiredComponent};
+use super::reflect::TypeInfo;
+#[cfg(feature = "serialize")]
+use super::serialize::ComponentSerializer;
+
+/// A type that identifies itself to an Ecs, such as a component, resource, stage or
+/// schedule label. Every Ecs keeps its own ids for them, looked up by their `TypeId`.
 pub trait Handle: 'static {
-    fn handle() -> &'static mut u16;
     fn name() -> &'static str;
 }
 
 impl<T: Any> Handle for T {
-    fn handle() -> &'static mut u16 {
-        static mut ID: u16 = u16::MAX;
-        unsafe { &mut ID }
-    }
-
     fn name() -> &'static str {
         type_name::<Self>()
     }
 }
 
-pub trait Resource : Handle {}
-pub trait Component : Handle {}
\ No newline at end of file
+/// Data that belongs to the world rather than to an entity. Systems on any thread 
+/// can read and write it, so it has to be `Send + Sync`. Data that isn't can 
+/// still be stored with `Ecs::insert_non_send`, which keeps it on one thread.
+pub trait Resource : Handle + Send + Sync {}
+
+/// Data that can be attached to an entity. Every item has a default, so a plain 
+/// `impl Component for T {}` works, but `#[derive(Component)]` can configure them
+/// with a `#[component(...)]` attribute next to the type.
+///
+/// Components are read and written by systems on any thread, so they have to be 
+/// `Send + Sync`. Keep data that isn't, such as an `Rc`, in a non-send resource 
+/// and store a key to it in the component instead.
+pub trait Component : Handle + Send + Sync {
+    /// Where the component is stored. 
+    const STORAGE: StorageType = StorageType::Table;
+
+    /// Sets the functions that are called as the component enters or leaves the world.
+    fn hooks(_hooks: &mut ComponentHooks) {}
+
+    /// Pushes every component that is spawned with its default value
+    /// when this component is spawned without it.
+    fn required(_required: &mut Vec<RequiredComponent>) {}
+
+    /// Makes a copy of the component, if it can be cloned. `#[component(clone)]`
+    /// sets this to its [`Clone`] impl.
+    fn cloner() -> Option<fn(&Self) -> Self> where Self: Sized {
+        None
+    }
+
+    /// The fields of the component, if they can be inspected at runtime.
+    fn reflect() -> Option<fn() -> TypeInfo> {
+        None
+    }
+
+    /// How the component is written to and read from text, if it can be.
+    #[cfg(feature = "serialize")]
+    fn serializer() -> Option<ComponentSerializer> {
+        None
+    }
+}
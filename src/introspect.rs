@@ -0,0 +1,219 @@
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::diagnostics::escape;
+use super::scheduler::Accessor;
+
+/// A snapshot of every schedule of an Ecs and how the systems of its stages are grouped.
+///
+/// Systems in the same group run in parallel, groups run one after another.
+/// Groups are only known once a schedule has been finalized, by `execute_startup`
+/// or the first time it runs.
+pub struct ScheduleInfo {
+    pub schedules: Vec<ScheduleStages>,
+}
+
+/// The stages of one schedule, in the order they run.
+pub struct ScheduleStages {
+    pub name: &'static str,
+    pub stages: Vec<StageInfo>,
+    /// Whether the schedule was running when the snapshot was taken, 
+    /// in which case its stages are missing.
+    pub running: bool,
+}
+
+pub struct StageInfo {
+    pub name: &'static str,
+    pub groups: Vec<GroupInfo>,
+    /// Systems that were added after the stage was last finalized.
+    pub pending: Vec<SystemInfo>,
+    /// Every pair of systems that had to be put in different groups, and why.
+    pub conflicts: Vec<ConflictInfo>,
+    /// Every pair of conflicting systems with no order between them, 
+    /// found by the last `finalize`.
+    pub ambiguities: Vec<ConflictInfo>,
+}
+
+pub struct GroupInfo {
+    pub systems: Vec<SystemInfo>,
+}
+
+pub struct SystemInfo {
+    pub name: &'static str,
+    pub access: Vec<Access>,
+    /// Whether the system takes the whole Ecs, and so runs in a group of its own.
+    pub exclusive: bool,
+    /// Whether the system runs when its group does.
+    pub enabled: bool,
+}
+
+/// An [`Accessor`] along with the name of the component or resource it accesses.
+#[derive(Copy, Clone, Debug)]
+pub struct Access {
+    pub accessor: Accessor,
+    pub name: &'static str,
+}
+
+/// Two systems that can't run in parallel, with every pair of accessors that conflict.
+pub struct ConflictInfo {
+    pub a: &'static str,
+    pub b: &'static str,
+    /// Whether either of them is exclusive, which conflicts with every other system.
+    pub exclusive: bool,
+    pub reasons: Vec<(Access, Access)>,
+}
+
+impl ConflictInfo {
+    /// Every reason the systems conflict, as text.
+    pub fn describe(&self) -> Vec<String> {
+        let exclusive = self.exclusive.then(|| "exclusive".to_string());
+
+        exclusive.into_iter()
+            .chain(self.reasons.iter().map(|(x, y)| format!("{} / {}", x, y)))
+            .collect()
+    }
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.accessor {
+            Accessor::Ref(_) => write!(f, "Ref<{}>", self.name),
+            Accessor::Mut(_) => write!(f, "Mut<{}>", self.name),
+            Accessor::ResRef(_) => write!(f, "ResRef<{}>", self.name),
+            Accessor::ResMut(_) => write!(f, "ResMut<{}>", self.name),
+            Accessor::MainThread => write!(f, "MainThread"),
+        }
+    }
+}
+
+impl ScheduleInfo {
+    /// Every stage of every schedule, in the order the schedules were added.
+    pub fn stages(&self) -> impl Iterator<Item = &StageInfo> {
+        self.schedules.iter().flat_map(|schedule| schedule.stages.iter())
+    }
+
+    /// The stages of the schedule called `name`.
+    pub fn schedule(&self, name: &str) -> Option<&ScheduleStages> {
+        self.schedules.iter().find(|schedule| schedule.name == name)
+    }
+
+    /// Renders the schedule as a Graphviz graph. Every stage and group is a cluster,
+    /// and conflicting systems are joined by a dashed edge labelled with the conflicts.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        // writing to a String can't fail.
+        let _ = writeln!(dot, "digraph schedule {{");
+        let _ = writeln!(dot, "    compound=true;");
+        let _ = writeln!(dot, "    node [shape=box];");
+
+        let stages = self.schedules.iter()
+            .flat_map(|schedule| schedule.stages.iter().map(move |stage| (schedule.name, stage)));
+
+        for (s, (schedule, stage)) in stages.enumerate() {
+            let _ = writeln!(dot, "    subgraph cluster_{} {{", s);
+            let _ = writeln!(dot, "        label=\"{}: {}\";", escape(schedule), escape(stage.name));
+
+            let node = |g: usize, n: usize| format!("s{}_g{}_n{}", s, g, n);
+
+            for (g, group) in stage.groups.iter().enumerate() {
+                let _ = writeln!(dot, "        subgraph cluster_{}_{} {{", s, g);
+                let _ = writeln!(dot, "            label=\"group {}\";", g);
+
+                for (n, system) in group.systems.iter().enumerate() {
+                    let _ = writeln!(dot, "            {} [label=\"{}\"];", node(g, n), escape(system.name));
+                }
+
+                let _ = writeln!(dot, "        }}");
+            }
+
+            for conflict in stage.conflicts.iter() {
+                let (Some(a), Some(b)) = (stage.find(conflict.a), stage.find(conflict.b)) else {
+                    continue
+                };
+
+                let label: Vec<String> = conflict.describe().iter()
+                    .map(|reason| escape(reason))
+                    .collect();
+
+                let _ = writeln!(dot, "        {} -> {} [style=dashed, dir=none, color=red, label=\"{}\"];",
+                    node(a.0, a.1), node(b.0, b.1), label.join("\\n"));
+            }
+
+            let _ = writeln!(dot, "    }}");
+        }
+
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+
+    /// Lists every schedule and stage with its groups, the access of every system, and
+    /// the conflicts that kept systems from running in parallel.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+
+        for schedule in self.schedules.iter() {
+            let _ = writeln!(report, "schedule {}{}", schedule.name, if schedule.running { " (running)" } else { "" });
+
+            for stage in schedule.stages.iter() {
+                report_stage(&mut report, stage);
+            }
+        }
+
+        report
+    }
+}
+
+fn report_stage(report: &mut String, stage: &StageInfo) {
+    let _ = writeln!(report, "  stage {} ({} groups)", stage.name, stage.groups.len());
+
+    for (g, group) in stage.groups.iter().enumerate() {
+        let _ = writeln!(report, "    group {}", g);
+
+        for system in group.systems.iter() {
+            let _ = writeln!(report, "      {}", describe(system));
+        }
+    }
+
+    if !stage.pending.is_empty() {
+        let _ = writeln!(report, "    pending");
+
+        for system in stage.pending.iter() {
+            let _ = writeln!(report, "      {}", describe(system));
+        }
+    }
+
+    for (title, conflicts) in [("conflicts", &stage.conflicts), ("ambiguities", &stage.ambiguities)] {
+        if conflicts.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(report, "    {}", title);
+
+        for conflict in conflicts.iter() {
+            let _ = writeln!(report, "      {} <-> {}: {}", conflict.a, conflict.b, conflict.describe().join(", "));
+        }
+    }
+}
+
+impl StageInfo {
+    /// The group and position within it of the system called `name`.
+    pub fn find(&self, name: &str) -> Option<(usize, usize)> {
+        self.groups.iter().enumerate().find_map(|(g, group)| {
+            group.systems.iter()
+                .position(|system| system.name == name)
+                .map(|n| (g, n))
+        })
+    }
+}
+
+fn describe(system: &SystemInfo) -> String {
+    let access: Vec<String> = system.access.iter().map(|access| access.to_string()).collect();
+    let disabled = if system.enabled { "" } else { " (disabled)" };
+
+    if system.exclusive {
+        format!("{} [exclusive]{}", system.name, disabled)
+    } else {
+        format!("{} [{}]{}", system.name, access.join(", "), disabled)
+    }
+}
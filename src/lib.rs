@@ -13,6 +13,15 @@ mod table;
 mod anon;
 mod package;
 mod commands;
+mod bundle;
+mod components;
+mod introspect;
+mod diagnostics;
+mod dynamic;
+mod reflect;
+mod prefab;
+#[cfg(feature = "serialize")]
+mod serialize;
 
 pub use ptr::*;
 pub use handle::*;
@@ -27,4 +36,15 @@ pub use archetypes::*;
 pub use ecs::*;
 pub use anon::*;
 pub use commands::*;
-pub use error::*;
\ No newline at end of file
+pub use bundle::*;
+pub use components::*;
+pub use introspect::*;
+pub use diagnostics::*;
+pub use dynamic::*;
+pub use reflect::*;
+pub use prefab::*;
+#[cfg(feature = "serialize")]
+pub use serialize::*;
+pub use error::*;
+
+pub use rylans_ecs_derive::{Bundle, Component, Reflect, Resource};
\ No newline at end of file
@@ -1,10 +1,13 @@
 
 use super::handle::Component;
-use super::anon::Anon;
-use super::archetypes::Archetype;
+use super::anon::{Anon, ComponentInfo};
+use super::components::Components;
+use super::error::Result;
+use super::trace;
 use super::archetypes::ComponentId;
 use super::archetypes::TableIndex;
 use super::archetypes::Column;
+use super::archetypes::Archetype;
 
 pub struct Package {
     pub(crate) components: Vec<Anon>,
@@ -17,28 +20,26 @@ impl Package {
         }
     }
 
-    pub(crate) fn archetype(&self) -> Archetype {
-        let mut archetype = Archetype::new();
-        for anon in self.components.iter() {
-            archetype = archetype.add(anon.id() as u64);
-        }
-        archetype
-    }
-
     pub(crate) fn remove(&mut self, id: ComponentId) {
         for i in 0..self.components.len() {
             if id == self.components[i].id() {
-                self.components[i].clear();
+                // dropping the Anon drops the component inside.
+                self.components[i].on_remove();
                 self.components.remove(i);
                 return;
             }
         }
     }
 
+    pub(crate) fn contains(&self, info: &ComponentInfo) -> bool {
+        self.components.iter().any(|anon| anon.info().is(info))
+    }
+
     pub(crate) fn insert_anon(&mut self, anon: Anon) {
         for i in 0..self.components.len() {
-            if anon.id() == self.components[i].id() {
-                self.components[i].clear();
+            if anon.info().is(self.components[i].info()) {
+                // the replaced component is dropped with its Anon.
+                self.components[i].on_remove();
                 self.components[i] = anon;
                 return;
             }
@@ -47,26 +48,58 @@ impl Package {
         self.components.push(anon);
     }
 
-    pub(crate) fn pop(&mut self) -> Option<Anon> {
-        self.components.pop()
-    }
-
     pub fn with<C: Component>(mut self, cmp: C) -> Self {
         self.components.push(Anon::new::<C>(cmp));
         self
     }
 
+    /// Adds a component that was built at runtime, such as one whose type is only
+    /// known to a script. The same component already in the package is replaced.
+    pub fn with_anon(mut self, anon: Anon) -> Self {
+        self.insert_anon(anon);
+        self
+    }
+
     pub fn build(self) -> Self {
         self
     }
+
+    /// Sets the id every component has in the Ecs that owns `components`.
+    pub(crate) fn resolve(&mut self, components: &Components) -> Result<()> {
+        for anon in self.components.iter_mut() {
+            trace!(anon.resolve(components));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Package {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub struct PackageIndex {
     pub table: TableIndex,
     pub col: Column,
 }
 
+/// An entity that [`Commands`](super::commands::Commands) queued to be spawned.
+///
+/// Where it lands is only known once the commands are flushed, after the entities
+/// destroyed and moved in the same flush have left their tables. Until the flush after
+/// that it resolves to its [`PackageIndex`] with `Ecs::resolve` or `Commands::resolve`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Reserved {
+    /// How many flushes had happened when the entity was reserved.
+    pub(crate) flush: u64,
+    pub(crate) archetype: Archetype,
+    /// Its position among the entities of its archetype spawned in the same flush.
+    pub(crate) offset: Column,
+}
+
 pub struct PackageIndexIter {
     pub table: TableIndex,
     pub col: usize,
@@ -105,6 +138,10 @@ impl PackageIndexChain {
         }
         count
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.iters.is_empty()
+    }
 }
 
 impl Iterator for PackageIndexChain {
@@ -126,4 +163,4 @@ impl Iterator for PackageIndexChain {
             None
         }
     }
-}
\ No newline at end of file
+}
@@ -1,91 +1,702 @@
 
-use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use std::any::type_name;
+use std::any::{type_name, Any};
+use std::marker::PhantomData;
 
-use super::handle::{Resource, Component};
-use super::error::Trace;
+use super::handle::Resource;
+use super::resources::ChangeTicks;
+use super::error::{EcsError, Result};
 use super::scheduler::Accessor;
 use super::ecs::Ecs;
 use super::ptr::Ptr;
 use crate::{start_trace, trace};
 
-pub trait System: Default {
-    fn execute(self) -> Trace<()>;
+/// A system parameter: what it borrows from the Ecs, and how to borrow it for one run.
+pub trait Fetch: Default {
+    /// # Safety
+    /// `ecs` must outlive the value, and nothing may access what [`access`](Self::access)
+    /// declares in a conflicting way while it is alive, including another value fetched
+    /// the same way. The scheduler ensures both for the parameters of the systems it runs.
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self>;
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()>;
 }
 
-pub trait Fetch: Default {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self>;
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()>;
+/// A [`Fetch`] parameter of function systems, named with `'static` in place of the
+/// run it borrows the Ecs for. `Item<'w>` is the same parameter borrowing it for `'w`,
+/// which is what the function is called with, so a function can't keep its parameters
+/// past the run even though they are named `'static` in its [`IntoSystem`] impl.
+pub trait SystemParam: Fetch + 'static {
+    type Item<'w>: Fetch;
+}
+
+/// The parameter `P` of a function system, borrowing the Ecs for `'w`.
+pub type SystemParamItem<'w, P> = <P as SystemParam>::Item<'w>;
+
+/// The type-erased body of a system: fetches its parameters and runs it.
+pub type SystemFn = Box<dyn Fn(Ptr<Ecs>, &mut Locals) -> Result<()> + Send + Sync>;
+
+/// The type-erased body of a run condition: fetches its parameters and evaluates it.
+pub type ConditionFn = Box<dyn Fn(Ptr<Ecs>, &mut Locals) -> Result<bool> + Send + Sync>;
+
+/// A [`Fetch`] that only reads the Ecs, so run conditions can take it.
+pub trait ReadOnlyFetch: Fetch {}
+
+/// Decides whether a system runs, from read-only parameters fetched just before it would.
+/// Like the system, they see what changed since the system last ran.
+/// See [`resource_changed`](super::resources::resource_changed) for an example.
+pub struct RunCondition {
+    pub(crate) evaluate: ConditionFn,
+    /// Merged into the accessors of the system, since the condition runs on its thread
+    /// alongside the same systems.
+    pub(crate) access: fn(&mut Vec<Accessor>, Ptr<Ecs>) -> Result<()>,
+    pub(crate) locals: Locals,
+}
+
+/// Anything that can be used as a [`RunCondition`]: functions that return `bool` and
+/// whose arguments all implement [`ReadOnlyFetch`], such as `fn paused(state: ResRef<State>) -> bool`.
+pub trait IntoCondition<Marker> {
+    fn into_condition(self) -> RunCondition;
+}
+
+impl IntoCondition<RunCondition> for RunCondition {
+    fn into_condition(self) -> RunCondition {
+        self
+    }
+}
+
+/// A system that is ready to be scheduled, with its parameters erased.
+pub struct BoxedSystem {
+    pub(crate) name: &'static str,
+    pub(crate) execute: SystemFn,
+    pub(crate) access: fn(&mut Vec<Accessor>, Ptr<Ecs>) -> Result<()>,
+    pub(crate) order: Order,
+    pub(crate) conditions: Vec<RunCondition>,
+    /// Whether the system takes the whole Ecs, and can't run alongside any other system.
+    pub(crate) exclusive: bool,
+}
+
+/// Where a system must run relative to other systems in its stage, by name.
+/// Systems that are not in the same stage are ignored. 
+#[derive(Clone, Default)]
+pub struct Order {
+    pub(crate) before: Vec<&'static str>,
+    pub(crate) after: Vec<&'static str>,
+    pub(crate) ambiguous_with: Vec<&'static str>,
+}
+
+/// Anything that can be added to a stage as a system. 
+///
+/// This is implemented for functions whose arguments all implement [`SystemParam`], such as 
+/// `fn movement(q: Query<(Mut<Pos>, Ref<Vel>)>, time: ResRef<Time>)`, for exclusive systems
+/// and for [`BoxedSystem`]s. `Marker` only exists to keep the kinds of impl apart and is 
+/// always inferred.
+pub trait IntoSystem<Marker> {
+    fn into_system(self) -> BoxedSystem;
+
+    /// Runs this system before `other` whenever both are in the same stage.
+    fn before<M>(self, other: impl IntoSystem<M>) -> BoxedSystem 
+    where
+        Self: Sized
+    {
+        let mut system = self.into_system();
+        system.order.before.push(other.into_system().name);
+        system
+    }
+
+    /// Runs this system after `other` whenever both are in the same stage.
+    fn after<M>(self, other: impl IntoSystem<M>) -> BoxedSystem 
+    where
+        Self: Sized
+    {
+        let mut system = self.into_system();
+        system.order.after.push(other.into_system().name);
+        system
+    }
+
+    /// Gives this system a name of its own in place of its type name, which closures
+    /// made by the same function share. Ordering, removing and enabling the system 
+    /// then go through a system with the same name, such as `other.named(name)`.
+    fn named(self, name: &'static str) -> BoxedSystem 
+    where
+        Self: Sized
+    {
+        let mut system = self.into_system();
+        system.name = name;
+        system
+    }
+
+    /// Only runs this system when `condition` holds. Conditions are checked before
+    /// every run, and a system skipped by one still sees changes made since it last ran.
+    fn run_if<M>(self, condition: impl IntoCondition<M>) -> BoxedSystem 
+    where
+        Self: Sized
+    {
+        let mut system = self.into_system();
+        system.conditions.push(condition.into_condition());
+        system
+    }
+
+    /// Allows this system and `other` to conflict without an order between them,
+    /// for when either order is correct. 
+    fn ambiguous_with<M>(self, other: impl IntoSystem<M>) -> BoxedSystem 
+    where
+        Self: Sized
+    {
+        let mut system = self.into_system();
+        system.order.ambiguous_with.push(other.into_system().name);
+        system
+    }
+}
+
+impl IntoSystem<BoxedSystem> for BoxedSystem {
+    fn into_system(self) -> BoxedSystem {
+        self
+    }
+}
+
+/// Marks the [`IntoSystem`] impl for exclusive systems, functions that take 
+/// `&mut Ecs` and can do anything with it, such as running another schedule. 
+/// They run on their own, after everything in the groups before them is done.
+pub struct Exclusive<Out>(PhantomData<Out>);
+
+impl<Func, Out> IntoSystem<Exclusive<Out>> for Func
+where
+    Func: Fn(&mut Ecs) -> Out + Send + Sync + 'static,
+    Out: SystemOutput,
+{
+    fn into_system(self) -> BoxedSystem {
+        BoxedSystem {
+            name: type_name::<Func>(),
+            // no other system runs while this one does.
+            execute: Box::new(move |ecs, _| (self)(unsafe { &mut *ecs.get_mut() }).into_result()),
+            access: |_, _| Ok(()),
+            order: Order::default(),
+            conditions: Vec::new(),
+            exclusive: true,
+        }
+    }
+}
+
+/// What a function system can return: either nothing, or a `Result` 
+/// whose error is passed to the error handler of the Ecs.
+pub trait SystemOutput {
+    fn into_result(self) -> Result<()>;
+}
+
+impl SystemOutput for () {
+    fn into_result(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<E> SystemOutput for std::result::Result<(), E> 
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    fn into_result(self) -> Result<()> {
+        self.map_err(|err| match err.into().downcast::<EcsError>() {
+            Ok(err) => *err,
+            Err(err) => EcsError::System(err),
+        })
+    }
 }
 
-pub struct ResRef<R>(pub(crate) *const R);
+macro_rules! impl_into_system {
+    ($($param:ident),*) => {
+        impl<Func, Out, $($param),*> IntoSystem<fn($($param),*) -> Out> for Func
+        where
+            Func: Fn($($param),*) -> Out + Send + Sync + 'static,
+            Func: for<'w> Fn($(SystemParamItem<'w, $param>),*) -> Out,
+            Out: SystemOutput,
+            $($param: SystemParam),*
+        {
+            fn into_system(self) -> BoxedSystem {
+                // calls `func` through the bound that takes the parameters for one run.
+                #[allow(non_snake_case, clippy::too_many_arguments)]
+                fn call<Out, $($param),*>(func: impl Fn($($param),*) -> Out, $($param: $param),*) -> Out {
+                    func($($param),*)
+                }
+
+                BoxedSystem {
+                    name: type_name::<Func>(),
+                    #[allow(non_snake_case)]
+                    execute: Box::new(move |ecs, locals| {
+                        // the scheduler only runs the system alongside systems it doesn't conflict with.
+                        let ($($param,)*) = trace!(unsafe { <($(SystemParamItem<'_, $param>,)*) as Fetch>::fetch(ecs, locals) });
+                        call(&self, $($param),*).into_result()
+                    }),
+                    access: <($($param,)*) as Fetch>::access,
+                    order: Order::default(),
+                    conditions: Vec::new(),
+                    exclusive: false,
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_into_condition {
+    ($($param:ident),*) => {
+        impl<Func, $($param),*> IntoCondition<fn($($param),*) -> bool> for Func
+        where
+            Func: Fn($($param),*) -> bool + Send + Sync + 'static,
+            Func: for<'w> Fn($(SystemParamItem<'w, $param>),*) -> bool,
+            $($param: SystemParam + ReadOnlyFetch),*
+        {
+            fn into_condition(self) -> RunCondition {
+                #[allow(non_snake_case, clippy::too_many_arguments)]
+                fn call<$($param),*>(func: impl Fn($($param),*) -> bool, $($param: $param),*) -> bool {
+                    func($($param),*)
+                }
+
+                RunCondition {
+                    #[allow(non_snake_case)]
+                    evaluate: Box::new(move |ecs, locals| {
+                        // the scheduler only runs the system alongside systems it doesn't conflict with.
+                        let ($($param,)*) = trace!(unsafe { <($(SystemParamItem<'_, $param>,)*) as Fetch>::fetch(ecs, locals) });
+                        Ok(call(&self, $($param),*))
+                    }),
+                    access: <($($param,)*) as Fetch>::access,
+                    locals: Locals::new(),
+                }
+            }
+        }
+    };
+}
+
+impl_into_condition!();
+impl_into_condition!(P1);
+impl_into_condition!(P1, P2);
+impl_into_condition!(P1, P2, P3);
+impl_into_condition!(P1, P2, P3, P4);
+
+impl_into_system!();
+impl_into_system!(P1);
+impl_into_system!(P1, P2);
+impl_into_system!(P1, P2, P3);
+impl_into_system!(P1, P2, P3, P4);
+impl_into_system!(P1, P2, P3, P4, P5);
+impl_into_system!(P1, P2, P3, P4, P5, P6);
+
+/// Shared access to a resource, along with when it was added and last changed.
+/// `'w` is the run of the system that fetched it, which the borrow can't outlive.
+pub struct ResRef<'w, R> {
+    pub(crate) value: *const R,
+    pub(crate) ticks: *const ChangeTicks,
+    /// The tick the system last ran at, changes after it are new to the system.
+    pub(crate) last_run: u64,
+    pub(crate) marker: PhantomData<&'w R>,
+}
 
-impl<R: Resource> Deref for ResRef<R> {
+impl<R> ResRef<'_, R> {
+    /// Whether the resource was inserted since the system last ran.
+    pub fn is_added(&self) -> bool {
+        unsafe { (*self.ticks).added > self.last_run }
+    }
+
+    /// Whether the resource was inserted or mutably dereferenced since the system last ran.
+    pub fn is_changed(&self) -> bool {
+        unsafe { (*self.ticks).changed > self.last_run }
+    }
+}
+
+impl<R> Deref for ResRef<'_, R> {
     type Target = R;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { &*(self.0) }
+        unsafe { &*(self.value) }
     }
 }
 
-impl<R: Resource> Default for ResRef<R> {
+impl<R> Default for ResRef<'_, R> {
     fn default() -> Self {
-        Self(std::ptr::null())
+        Self {
+            value: std::ptr::null(),
+            ticks: std::ptr::null(),
+            last_run: 0,
+            marker: PhantomData,
+        }
     }
 }
 
-impl<R: Resource> Fetch for ResRef<R> {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        ecs.get_resource_ref::<R>()
+impl<R: Resource> Fetch for ResRef<'_, R> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        // the borrow lives for as long as the system run it was fetched for.
+        unsafe { ecs.as_ref() }.resources.get_resource_ref::<R>(locals.last_run)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let Some(handle) = ecs.resources.handle::<R>() else {
+            start_trace!(EcsError::MissingResource(type_name::<R>()))
+        };
+
+        v.push(Accessor::ResRef(handle));
+
+        Ok(())
     }
+}
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        if *R::handle() == u16::MAX {
-            start_trace!(format!("Tried to collect accessor for resource {}, but it had not been declared!", type_name::<R>()))
+/// `None` while the resource is missing, instead of failing the system.
+impl<R: Resource> Fetch for Option<ResRef<'_, R>> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        if !ecs.resources.contains_resource::<R>() {
+            return Ok(None);
         }
 
-        v.push(Accessor::ResRef(*R::handle()));
+        unsafe { ecs.as_ref() }.resources.get_resource_ref::<R>(locals.last_run).map(Some)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        // the resource may be inserted after the stage is planned, so it needs a handle now.
+        let handle = unsafe { (*ecs.get_mut()).resources.register::<R>() };
+        v.push(Accessor::ResRef(handle));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
-pub struct ResMut<R: Resource>(pub(crate) *mut R);
+impl<R: Resource> SystemParam for ResRef<'static, R> {
+    type Item<'w> = ResRef<'w, R>;
+}
+
+impl<R: Resource> SystemParam for Option<ResRef<'static, R>> {
+    type Item<'w> = Option<ResRef<'w, R>>;
+}
+
+impl<R: Resource> ReadOnlyFetch for ResRef<'_, R> {}
+impl<R: Resource> ReadOnlyFetch for Option<ResRef<'_, R>> {}
+
+/// Exclusive access to a resource. Mutably dereferencing it marks the resource as changed.
+pub struct ResMut<'w, R> {
+    pub(crate) value: *mut R,
+    pub(crate) ticks: *mut ChangeTicks,
+    pub(crate) last_run: u64,
+    /// The tick changes made through this are stamped with.
+    pub(crate) this_run: u64,
+    pub(crate) marker: PhantomData<&'w mut R>,
+}
+
+impl<R> ResMut<'_, R> {
+    /// Whether the resource was inserted since the system last ran.
+    pub fn is_added(&self) -> bool {
+        unsafe { (*self.ticks).added > self.last_run }
+    }
+
+    /// Whether the resource was inserted or mutably dereferenced since the system last ran.
+    pub fn is_changed(&self) -> bool {
+        unsafe { (*self.ticks).changed > self.last_run }
+    }
+
+    /// Marks the resource as changed without touching it.
+    pub fn set_changed(&mut self) {
+        unsafe { (*self.ticks).changed = self.this_run; }
+    }
 
-impl<R: Resource> Deref for ResMut<R> {
+    /// Changes the resource without marking it as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut R {
+        unsafe { &mut *(self.value) }
+    }
+}
+
+impl<R> Deref for ResMut<'_, R> {
     type Target = R;
 
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*(self.value) }
+    }
+}
+
+impl<R> DerefMut for ResMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.set_changed();
+        unsafe { &mut *(self.value) }
+    }
+}
+
+impl<R> Default for ResMut<'_, R> {
+    fn default() -> Self {
+        Self {
+            value: std::ptr::null_mut(),
+            ticks: std::ptr::null_mut(),
+            last_run: 0,
+            this_run: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Resource> Fetch for ResMut<'_, R> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        unsafe { ecs.as_ref() }.resources.get_resource_mut::<R>(locals.last_run, locals.this_run)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let Some(handle) = ecs.resources.handle::<R>() else {
+            start_trace!(EcsError::MissingResource(type_name::<R>()))
+        };
+
+        v.push(Accessor::ResMut(handle));
+
+        Ok(())
+    }
+}
+
+/// `None` while the resource is missing, instead of failing the system.
+impl<R: Resource> Fetch for Option<ResMut<'_, R>> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        if !ecs.resources.contains_resource::<R>() {
+            return Ok(None);
+        }
+
+        unsafe { ecs.as_ref() }.resources.get_resource_mut::<R>(locals.last_run, locals.this_run).map(Some)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        // the resource may be inserted after the stage is planned, so it needs a handle now.
+        let handle = unsafe { (*ecs.get_mut()).resources.register::<R>() };
+        v.push(Accessor::ResMut(handle));
+
+        Ok(())
+    }
+}
+
+impl<R: Resource> SystemParam for ResMut<'static, R> {
+    type Item<'w> = ResMut<'w, R>;
+}
+
+impl<R: Resource> SystemParam for Option<ResMut<'static, R>> {
+    type Item<'w> = Option<ResMut<'w, R>>;
+}
+
+/// Shared access to a resource that isn't `Send`, inserted with `insert_non_send`.
+/// A system with one always runs on the thread that runs its schedule.
+pub struct NonSend<'w, R>(pub(crate) ResRef<'w, R>);
+
+impl<R> NonSend<'_, R> {
+    pub fn is_added(&self) -> bool {
+        self.0.is_added()
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.0.is_changed()
+    }
+}
+
+impl<R> Deref for NonSend<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<R> Default for NonSend<'_, R> {
+    fn default() -> Self {
+        Self(ResRef::default())
+    }
+}
+
+impl<R: 'static> Fetch for NonSend<'_, R> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        unsafe { ecs.as_ref() }.resources.get_non_send_ref::<R>(locals.last_run)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let Some(handle) = ecs.resources.handle::<R>() else {
+            start_trace!(EcsError::MissingResource(type_name::<R>()))
+        };
+
+        v.push(Accessor::ResRef(handle));
+        v.push(Accessor::MainThread);
+
+        Ok(())
+    }
+}
+
+impl<R: 'static> SystemParam for NonSend<'static, R> {
+    type Item<'w> = NonSend<'w, R>;
+}
+
+impl<R: 'static> ReadOnlyFetch for NonSend<'_, R> {}
+
+/// Exclusive access to a resource that isn't `Send`, inserted with `insert_non_send`.
+/// A system with one always runs on the thread that runs its schedule.
+pub struct NonSendMut<'w, R>(pub(crate) ResMut<'w, R>);
+
+impl<R> NonSendMut<'_, R> {
+    pub fn is_added(&self) -> bool {
+        self.0.is_added()
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.0.is_changed()
+    }
+
+    pub fn set_changed(&mut self) {
+        self.0.set_changed()
+    }
+}
+
+impl<R> Deref for NonSendMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<R> DerefMut for NonSendMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<R> Default for NonSendMut<'_, R> {
+    fn default() -> Self {
+        Self(ResMut::default())
+    }
+}
+
+impl<R: 'static> Fetch for NonSendMut<'_, R> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        unsafe { ecs.as_ref() }.resources.get_non_send_mut::<R>(locals.last_run, locals.this_run)
+    }
+
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let Some(handle) = ecs.resources.handle::<R>() else {
+            start_trace!(EcsError::MissingResource(type_name::<R>()))
+        };
+
+        v.push(Accessor::ResMut(handle));
+        v.push(Accessor::MainThread);
+
+        Ok(())
+    }
+}
+
+impl<R: 'static> SystemParam for NonSendMut<'static, R> {
+    type Item<'w> = NonSendMut<'w, R>;
+}
+
+/// The storage behind every [`Local`] of a system, owned by its scheduler node.
+///
+/// Locals are handed out by position, in the order the system fetches them,
+/// so the same parameter gets the same slot on every run.
+#[derive(Default)]
+pub struct Locals {
+    slots: Vec<Box<dyn Any + Send>>,
+    cursor: usize,
+    /// Whether the parameters should count what they touch for [`Diagnostics`](super::diagnostics::Diagnostics).
+    pub(crate) profile: bool,
+    /// Entities matched by the queries of the system during its last run.
+    pub(crate) entities: usize,
+    /// Commands submitted by the system during its last run.
+    pub(crate) commands: usize,
+    /// The change tick of the run before this one, and of this one.
+    pub(crate) last_run: u64,
+    pub(crate) this_run: u64,
+}
+
+// The slots are only reachable through `&mut self`, so sharing a `&Locals` gives no access to them.
+unsafe impl Sync for Locals {}
+
+impl Locals {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            cursor: 0,
+            profile: false,
+            entities: 0,
+            commands: 0,
+            last_run: 0,
+            this_run: 0,
+        }
+    }
+
+    /// Starts handing out slots from the first one again, resets the counters,
+    /// and starts a run at the change tick `tick`.
+    pub(crate) fn rewind(&mut self, profile: bool, tick: u64) {
+        self.cursor = 0;
+        self.profile = profile;
+        self.entities = 0;
+        self.commands = 0;
+        self.last_run = self.this_run;
+        self.this_run = tick;
+    }
+
+    /// Starts handing out slots from the first one again, for a run condition
+    /// that sees what changed since its system last ran at `last_run`.
+    pub(crate) fn rewind_condition(&mut self, last_run: u64) {
+        self.cursor = 0;
+        self.last_run = last_run;
+        self.this_run = last_run;
+    }
+
+    fn next<T: Default + Send + 'static>(&mut self) -> Result<*mut T> {
+        if self.cursor == self.slots.len() {
+            self.slots.push(Box::new(T::default()));
+        }
+
+        let slot = &mut self.slots[self.cursor];
+        self.cursor += 1;
+
+        if let Some(val) = slot.downcast_mut::<T>() {
+            Ok(val as *mut T)
+        } else {
+            start_trace!(EcsError::Internal("local slot held a value of a different type"))
+        }
+    }
+}
+
+/// State that belongs to a single system and persists between its runs.
+/// 
+/// It starts out as `T::default()` and never conflicts with other systems.
+pub struct Local<T: Default + Send + 'static>(*mut T);
+
+impl<T: Default + Send + 'static> Deref for Local<T> {
+    type Target = T;
+
     fn deref(&self) -> &Self::Target {
         unsafe { &*(self.0) }
     }
 }
 
-impl<R: Resource> DerefMut for ResMut<R> {
+impl<T: Default + Send + 'static> DerefMut for Local<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         unsafe { &mut *(self.0) }
     }
 }
 
-impl<R: Resource> Default for ResMut<R> {
+impl<T: Default + Send + 'static> Default for Local<T> {
     fn default() -> Self {
         Self(std::ptr::null_mut())
     }
 }
 
-impl<R: Resource> Fetch for ResMut<R> {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        ecs.get_resource_mut::<R>()
+impl<T: Default + Send + 'static> Fetch for Local<T> {
+    unsafe fn fetch(_: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok(Local(trace!(locals.next::<T>())))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        if *R::handle() == u16::MAX {
-            start_trace!(format!("Tried to collect accessor for resource {}, but it had not been declared!", type_name::<R>()))
-        }
+    fn access(_: &mut Vec<Accessor>, _: Ptr<Ecs>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Default + Send + 'static> SystemParam for Local<T> {
+    type Item<'w> = Local<T>;
+}
+
+/// The local belongs to the condition, so writing to it changes nothing in the Ecs.
+impl<T: Default + Send + 'static> ReadOnlyFetch for Local<T> {}
 
-        v.push(Accessor::ResMut(*R::handle()));
+impl Fetch for () {
+    unsafe fn fetch(_: Ptr<Ecs>, _: &mut Locals) -> Result<Self> {
+        Ok(())
+    }
 
-        Trace::Ok(())
+    fn access(_: &mut Vec<Accessor>, _: Ptr<Ecs>) -> Result<()> {
+        Ok(())
     }
 }
 
@@ -93,11 +704,11 @@ impl<P1> Fetch for (P1,)
 where
     P1: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((trace!(P1::fetch(ecs)),))
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((trace!(P1::fetch(ecs, locals)),))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         P1::access(v, ecs)
     }
 }
@@ -107,18 +718,18 @@ where
     P1: Fetch,
     P2: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((
-            trace!(P1::fetch(ecs.clone())), 
-            trace!(P2::fetch(ecs.clone()))
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((
+            trace!(P1::fetch(ecs.clone(), locals)), 
+            trace!(P2::fetch(ecs.clone(), locals))
         ))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         trace!(P1::access(v, ecs.clone()));
         trace!(P2::access(v, ecs.clone()));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
@@ -128,20 +739,20 @@ where
     P2: Fetch,
     P3: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((
-            trace!(P1::fetch(ecs.clone())), 
-            trace!(P2::fetch(ecs.clone())),
-            trace!(P3::fetch(ecs.clone())),
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((
+            trace!(P1::fetch(ecs.clone(), locals)), 
+            trace!(P2::fetch(ecs.clone(), locals)),
+            trace!(P3::fetch(ecs.clone(), locals)),
         ))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         trace!(P1::access(v, ecs.clone()));
         trace!(P2::access(v, ecs.clone()));
         trace!(P3::access(v, ecs.clone()));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
@@ -152,22 +763,22 @@ where
     P3: Fetch,
     P4: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((
-            trace!(P1::fetch(ecs.clone())), 
-            trace!(P2::fetch(ecs.clone())),
-            trace!(P3::fetch(ecs.clone())),
-            trace!(P4::fetch(ecs.clone())),
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((
+            trace!(P1::fetch(ecs.clone(), locals)), 
+            trace!(P2::fetch(ecs.clone(), locals)),
+            trace!(P3::fetch(ecs.clone(), locals)),
+            trace!(P4::fetch(ecs.clone(), locals)),
         ))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         trace!(P1::access(v, ecs.clone()));
         trace!(P2::access(v, ecs.clone()));
         trace!(P3::access(v, ecs.clone()));
         trace!(P4::access(v, ecs.clone()));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
@@ -179,24 +790,24 @@ where
     P4: Fetch,
     P5: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((
-            trace!(P1::fetch(ecs.clone())), 
-            trace!(P2::fetch(ecs.clone())),
-            trace!(P3::fetch(ecs.clone())),
-            trace!(P4::fetch(ecs.clone())),
-            trace!(P5::fetch(ecs.clone())),
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((
+            trace!(P1::fetch(ecs.clone(), locals)), 
+            trace!(P2::fetch(ecs.clone(), locals)),
+            trace!(P3::fetch(ecs.clone(), locals)),
+            trace!(P4::fetch(ecs.clone(), locals)),
+            trace!(P5::fetch(ecs.clone(), locals)),
         ))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         trace!(P1::access(v, ecs.clone()));
         trace!(P2::access(v, ecs.clone()));
         trace!(P3::access(v, ecs.clone()));
         trace!(P4::access(v, ecs.clone()));
         trace!(P5::access(v, ecs.clone()));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
 
@@ -209,18 +820,18 @@ where
     P5: Fetch,
     P6: Fetch,
 {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok((
-            trace!(P1::fetch(ecs.clone())), 
-            trace!(P2::fetch(ecs.clone())),
-            trace!(P3::fetch(ecs.clone())),
-            trace!(P4::fetch(ecs.clone())),
-            trace!(P5::fetch(ecs.clone())),
-            trace!(P6::fetch(ecs.clone())),
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        Ok((
+            trace!(P1::fetch(ecs.clone(), locals)), 
+            trace!(P2::fetch(ecs.clone(), locals)),
+            trace!(P3::fetch(ecs.clone(), locals)),
+            trace!(P4::fetch(ecs.clone(), locals)),
+            trace!(P5::fetch(ecs.clone(), locals)),
+            trace!(P6::fetch(ecs.clone(), locals)),
         ))
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
         trace!(P1::access(v, ecs.clone()));
         trace!(P2::access(v, ecs.clone()));
         trace!(P3::access(v, ecs.clone()));
@@ -228,6 +839,6 @@ where
         trace!(P5::access(v, ecs.clone()));
         trace!(P6::access(v, ecs.clone()));
 
-        Trace::Ok(())
+        Ok(())
     }
 }
\ No newline at end of file
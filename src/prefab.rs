@@ -0,0 +1,208 @@
+
+use super::anon::Anon;
+use super::bundle::DynamicBundle;
+use super::handle::Component;
+use super::package::{Package, PackageIndex};
+use super::error::Result;
+use super::trace;
+#[cfg(feature = "serialize")]
+use super::{archetypes::ComponentId, components::Components, error::EcsError, start_trace};
+
+/// A template for an entity, and optionally its children, that can be spawned many times.
+///
+/// Every spawn clones the components of the prefab, so they all have to be cloneable,
+/// see `#[component(clone)]`. Prefabs are built in code, or loaded from RON text
+/// with [`Prefab::from_ron`], which reads each component with its serializer:
+///
+/// ```text
+/// (
+///     components: {
+///         "Health": (100),
+///         "game::Position": (x: 0.0, y: 0.0),
+///     },
+///     children: [
+///         (components: { "Weapon": (damage: 5) }),
+///     ],
+/// )
+/// ```
+///
+/// Components are named by their full type name, or by the last part of it as
+/// long as that is unique.
+pub struct Prefab {
+    components: Vec<Anon>,
+    children: Vec<Prefab>,
+}
+
+/// The entities spawned from a [`Prefab`], or the [`Reserved`](super::package::Reserved)
+/// entities when commands spawn it.
+///
+/// The Ecs has no hierarchy of its own, so the children are spawned as entities
+/// of their own, and only this tree records which prefab they came from.
+#[derive(Clone, Debug)]
+pub struct PrefabInstance<E = PackageIndex> {
+    pub root: E,
+    pub children: Vec<PrefabInstance<E>>,
+}
+
+impl<E: Copy> PrefabInstance<E> {
+    /// The same tree with every entity mapped by `f`, such as `|reserved| ecs.resolve(reserved)`.
+    pub fn try_map<T>(&self, f: &mut impl FnMut(E) -> Result<T>) -> Result<PrefabInstance<T>> {
+        let root = trace!(f(self.root));
+
+        let mut children = Vec::with_capacity(self.children.len());
+        for child in self.children.iter() {
+            children.push(trace!(child.try_map(f)));
+        }
+
+        Ok(PrefabInstance { root, children })
+    }
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self {
+            components: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Adds a component, replacing the one with the same type.
+    pub fn with<C: Component>(self, cmp: C) -> Self {
+        self.with_anon(Anon::new(cmp))
+    }
+
+    /// Adds a component that was built at runtime, replacing the same component.
+    pub fn with_anon(mut self, anon: Anon) -> Self {
+        self.components.retain(|other| !other.info().is(anon.info()));
+        self.components.push(anon);
+        self
+    }
+
+    /// Adds a child that is spawned along with every instance.
+    pub fn with_child(mut self, child: Prefab) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn components(&self) -> &[Anon] {
+        &self.components
+    }
+
+    pub fn children(&self) -> &[Prefab] {
+        &self.children
+    }
+
+    /// Clones the components of the prefab into a [`Package`], with the components
+    /// of `overrides` in place of the same ones in the prefab.
+    /// Children are left out, see `Ecs::spawn_prefab`.
+    pub fn instantiate<B: DynamicBundle>(&self, overrides: B) -> Result<Package> {
+        let mut infos = Vec::new();
+        overrides.infos_dyn(&mut infos);
+
+        let mut infos = infos.into_iter();
+        let mut replaced = Vec::new();
+        overrides.take_dyn(&mut |ptr| {
+            let info = infos.next().unwrap();
+            replaced.push(unsafe { Anon::from_raw(info, ptr) });
+        });
+
+        let mut package = Package::new();
+        for anon in self.components.iter() {
+            // overridden components are never cloned, so they don't have to be cloneable.
+            if !replaced.iter().any(|other| other.info().is(anon.info())) {
+                package.components.push(trace!(anon.try_clone()));
+            }
+        }
+
+        package.components.extend(replaced);
+
+        Ok(package)
+    }
+
+    /// Reads a prefab from RON text, looking every component up in `components`.
+    #[cfg(feature = "serialize")]
+    pub fn from_ron(text: &str, components: &Components) -> Result<Prefab> {
+        let value = match ron::from_str::<ron::Value>(text) {
+            Ok(value) => value,
+            Err(err) => start_trace!(EcsError::InvalidPrefab(err.to_string())),
+        };
+
+        Self::from_value(value, components)
+    }
+
+    #[cfg(feature = "serialize")]
+    fn from_value(value: ron::Value, components: &Components) -> Result<Prefab> {
+        let ron::Value::Map(map) = value else {
+            start_trace!(EcsError::InvalidPrefab("expected a prefab like (components: {...}, children: [...])".to_string()))
+        };
+
+        let mut prefab = Prefab::new();
+
+        for (key, value) in map.into_iter() {
+            match (key, value) {
+                (ron::Value::String(key), ron::Value::Map(map)) if key == "components" => {
+                    for (name, value) in map.into_iter() {
+                        let ron::Value::String(name) = name else {
+                            start_trace!(EcsError::InvalidPrefab("component names have to be strings".to_string()))
+                        };
+
+                        let anon = trace!(Self::component(&name, value, components));
+                        prefab = prefab.with_anon(anon);
+                    }
+                },
+                (ron::Value::String(key), ron::Value::Seq(children)) if key == "children" => {
+                    for child in children {
+                        prefab.children.push(trace!(Self::from_value(child, components)));
+                    }
+                },
+                (key, _) => start_trace!(EcsError::InvalidPrefab(format!("unexpected field {:?}", key))),
+            }
+        }
+
+        Ok(prefab)
+    }
+
+    /// Reads the component called `name` from `value` with its serializer.
+    #[cfg(feature = "serialize")]
+    fn component(name: &str, value: ron::Value, components: &Components) -> Result<Anon> {
+        let Some(info) = find(name, components).and_then(|id| components.info(id)) else {
+            start_trace!(EcsError::UnknownComponentName(name.to_string()))
+        };
+
+        let Some(serializer) = info.serializer else {
+            start_trace!(EcsError::NotSerializable(info.name))
+        };
+
+        // fail while loading rather than on the first spawn.
+        if info.clone.is_none() {
+            start_trace!(EcsError::NotCloneable(info.name))
+        }
+
+        match serializer.deserialize(value) {
+            Ok(anon) => Ok(anon),
+            Err(err) => start_trace!(EcsError::InvalidPrefab(format!("{}: {}", info.name, err))),
+        }
+    }
+}
+
+impl Default for Prefab {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The component called `name`, or the only one whose type name ends in `::name`.
+#[cfg(feature = "serialize")]
+fn find(name: &str, components: &Components) -> Option<ComponentId> {
+    if let Some(id) = components.id(name) {
+        return Some(id);
+    }
+
+    let suffix = format!("::{}", name);
+    let mut matches = components.iter().filter(|info| info.name.ends_with(&suffix));
+
+    match (matches.next(), matches.next()) {
+        (Some(info), None) => Some(info.id),
+        _ => None,
+    }
+}
@@ -12,21 +12,35 @@ impl<T> Ptr<T> {
         }
     }
 
-    pub fn clone(&self) -> Self {
-        Self {
-            ptr: self.ptr
-        }
-    }
-
     pub fn get_mut(&self) -> *mut T {
         self.ptr as *mut T
     }
 
+    /// The value, borrowed for `'a` instead of for as long as the pointer.
+    ///
+    /// # Safety
+    /// The value must stay alive and in place for as long as `'a`.
+    pub unsafe fn as_ref<'a>(&self) -> &'a T {
+        &*(self.ptr)
+    }
+
     pub const fn null() -> Self {
         Self {
             ptr: std::ptr::null()
         }
     }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+}
+
+impl<T> Clone for Ptr<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr
+        }
+    }
 }
 
 impl<T> Deref for Ptr<T> {
@@ -75,9 +89,19 @@ impl<T> Unsafe<T> {
     }
 
     pub fn get(&self) -> *mut T {
-        unsafe { &self.val as *const T as *mut T }
+        &self.val as *const T as *mut T
+    }
+
+    pub fn into_inner(self) -> T {
+        self.val
     }
 }
 
-unsafe impl<T> Send for Ptr<T> {}
-unsafe impl<T> Sync for Ptr<T> {}
\ No newline at end of file
+// A `Ptr` is a shared reference whose lifetime is left to the caller, so it can
+// cross threads whenever a `&T` can. Writing through `get_mut` from several threads
+// is only sound where the scheduler's access checks keep the writes apart.
+unsafe impl<T: Sync> Send for Ptr<T> {}
+unsafe impl<T: Sync> Sync for Ptr<T> {}
+
+// A `PtrMut` stands in for a `&mut T`, so it moves between threads when one can.
+unsafe impl<T: Send> Send for PtrMut<T> {}
\ No newline at end of file
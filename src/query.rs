@@ -1,152 +1,187 @@
 
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
 
 use indexmap::IndexSet;
 
-use crate::anon::Anon;
-
 use super::ptr::Ptr;
 use super::ecs::Ecs;
-use super::error::Trace;
+use super::error::Result;
 use super::scheduler::Accessor;
 use super::handle::Component;
 use super::anon::AnonIterChain;
-use super::archetypes::TableIndex;
+use super::archetypes::{ComponentId, TableIndex};
+use super::components::Components;
 use super::package::PackageIndexChain;
 use super::package::PackageIndex;
 use super::archetypes::Archetype;
-use super::handle::Handle;
 use super::trace;
-use super::params::Fetch;
-
-pub struct Query<Q: IntoQuery> {
+use super::params::{Fetch, ReadOnlyFetch, SystemParam};
+use super::params::Locals;
+
+/// Iterates over the entities that have every component in `Q`.
+///
+/// `'w` is the run of the system that fetched the query. The items borrow the
+/// query, so no component reference outlives the run, or a later structural
+/// change that could move the component.
+pub struct Query<'w, Q: IntoQuery> {
     ecs: Ptr<Ecs>,
-    marker: PhantomData<Q>,
+    /// The id of every queried component in the Ecs, looked up once per run.
+    ids: Vec<ComponentId>,
+    marker: PhantomData<(&'w Ecs, Q)>,
+}
+
+impl<'w, Q: ReadOnlyQuery> Query<'w, Q> {
+    /// Iterates over every entity that has all of the queried components.
+    /// Only queries that read every component can be iterated more than once at a time.
+    pub fn iter(&self) -> Result<Q::Item<'_>> {
+        // the items borrow self, and self only lives as long as the system run.
+        unsafe { Q::into_query(self.ecs.clone(), &self.ids) }
+    }
+}
+
+impl<'w, Q: IntoQuery> Query<'w, Q> {
+    /// Iterates over every entity that has all of the queried components.
+    /// The items borrow the query mutably, so no two of them alias a [`Mut`] component.
+    pub fn iter_mut(&mut self) -> Result<Q::Item<'_>> {
+        // the items borrow self, and self only lives as long as the system run.
+        unsafe { Q::into_query(self.ecs.clone(), &self.ids) }
+    }
 }
 
-impl<Q: IntoQuery> Default for Query<Q> {
+impl<Q: IntoQuery> Default for Query<'_, Q> {
     fn default() -> Self {
-        Self { ecs: Ptr::null(), marker: Default::default() }
+        Self { ecs: Ptr::null(), ids: Vec::new(), marker: Default::default() }
     }
 }
 
-impl<Q: IntoQuery> Fetch for Query<Q> {
-    fn fetch(ecs: Ptr<Ecs>) -> Trace<Self> {
-        Trace::Ok(Self {
+impl<Q: IntoQuery> Fetch for Query<'_, Q> {
+    unsafe fn fetch(ecs: Ptr<Ecs>, locals: &mut Locals) -> Result<Self> {
+        let ids = trace!(Q::ids(&ecs.components));
+
+        if locals.profile {
+            locals.entities += ecs.archetypes.count(Archetype::of(&ids));
+        }
+
+        Ok(Self {
             ecs: ecs.clone(),
+            ids,
             marker: PhantomData,
         })
     }
 
-    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        Q::accessors(v, ecs);
-        Trace::Ok(())
+    fn access(v: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        Q::accessors(v, ecs)
     }
 }
 
-pub trait IntoQuery: 'static {
-    type Item: Iterator;
+impl<Q: IntoQuery> SystemParam for Query<'static, Q> {
+    type Item<'w> = Query<'w, Q>;
+}
+
+impl<Q: ReadOnlyQuery> ReadOnlyFetch for Query<'_, Q> {}
 
-    fn into_query(ecs: Ptr<Ecs>) -> Trace<Self::Item>;
-    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()>;
+pub trait IntoQuery: 'static {
+    type Item<'w>: Iterator;
+
+    /// The id of every queried component in `components`, in order. The query
+    /// is registered in the query cache under the archetype of these ids.
+    fn ids(components: &Components) -> Result<Vec<ComponentId>>;
+
+    /// # Safety
+    /// `ids` must come from [`ids`](Self::ids) for the same Ecs. Nothing may move 
+    /// or remove the queried components, or access them in a way that conflicts 
+    /// with `Q`, for as long as `'w`.
+    unsafe fn into_query<'w>(ecs: Ptr<Ecs>, ids: &[ComponentId]) -> Result<Self::Item<'w>>;
+    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()>;
 }
 
 pub trait QueryParam: 'static {
     type Item: Component;
-    type Output;
+    type Output<'w>;
 
-    fn collect(indices: &IndexSet<TableIndex>, ecs: Ptr<Ecs>) -> AnonIterChain<Self::Item>;
-    fn accessors(accessors: &mut Vec<Accessor>);
-    fn wrap(data: &'static mut Self::Item) -> Self::Output;
+    /// # Safety
+    /// The same as [`IntoQuery::into_query`].
+    unsafe fn collect<'w>(indices: &IndexSet<TableIndex>, id: ComponentId, ecs: Ptr<Ecs>) -> Result<AnonIterChain<'w, Self::Item>>;
+    fn accessors(accessors: &mut Vec<Accessor>, id: ComponentId) -> Result<()>;
+    fn wrap(data: &mut Self::Item) -> Self::Output<'_>;
 }
 
-pub struct Ref<C: Component>(&'static C);
+/// A [`QueryParam`] that only hands out shared references.
+pub trait ReadOnlyParam: QueryParam {}
 
-impl<C: Component> Deref for Ref<C> {
-    type Target = C;
+/// An [`IntoQuery`] whose every parameter is a [`ReadOnlyParam`], so iterating it
+/// through a shared [`Query`] can't alias a mutable reference.
+pub trait ReadOnlyQuery: IntoQuery {}
 
-    fn deref(&self) -> &Self::Target {
-        self.0
-    }
-}
+impl<T1: ReadOnlyParam> ReadOnlyQuery for (T1,) {}
+impl<T1: ReadOnlyParam, T2: ReadOnlyParam> ReadOnlyQuery for (T1, T2) {}
+impl<T1: ReadOnlyParam, T2: ReadOnlyParam, T3: ReadOnlyParam> ReadOnlyQuery for (T1, T2, T3) {}
+impl<T1: ReadOnlyParam, T2: ReadOnlyParam, T3: ReadOnlyParam, T4: ReadOnlyParam> ReadOnlyQuery for (T1, T2, T3, T4) {}
+
+/// Queries `C` by shared reference.
+pub struct Ref<C: Component>(PhantomData<C>);
+
+impl<C: Component> ReadOnlyParam for Ref<C> {}
 
 impl<C: Component> QueryParam for Ref<C> {
     type Item = C;
 
-    type Output = Ref<C>;
+    type Output<'w> = &'w C;
 
-    fn collect(indices: &IndexSet<TableIndex>, ecs: Ptr<Ecs>) -> AnonIterChain<Self::Item> {
-        ecs.archetypes.collect(indices)
+    unsafe fn collect<'w>(indices: &IndexSet<TableIndex>, id: ComponentId, ecs: Ptr<Ecs>) -> Result<AnonIterChain<'w, Self::Item>> {
+        ecs.as_ref().archetypes.collect(indices, id)
     }
 
-    fn accessors(accessors: &mut Vec<Accessor>) {
-        if *C::handle() == u16::MAX {
-            panic!("Tried to collect accessors for undeclared Component {} in a Query!", std::any::type_name::<C>());
-        }
+    fn accessors(accessors: &mut Vec<Accessor>, id: ComponentId) -> Result<()> {
+        accessors.push(Accessor::Ref(id));
 
-        accessors.push(Accessor::Ref(*C::handle()))
+        Ok(())
     }
 
-    fn wrap(data: &'static mut Self::Item) -> Self::Output {
-        Self(data)
+    fn wrap(data: &mut Self::Item) -> Self::Output<'_> {
+        data
     }
 }
 
-pub struct Mut<C: Component>(&'static mut C);
-
-impl<C: Component> Deref for Mut<C> {
-    type Target = C;
-
-    fn deref(&self) -> &Self::Target {
-        self.0
-    }
-}
-
-impl<C: Component> DerefMut for Mut<C> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0
-    }
-}
+/// Queries `C` by mutable reference.
+pub struct Mut<C: Component>(PhantomData<C>);
 
 impl<C: Component> QueryParam for Mut<C> {
     type Item = C;
 
-    type Output = Mut<C>;
+    type Output<'w> = &'w mut C;
 
-    fn collect(indices: &IndexSet<TableIndex>, ecs: Ptr<Ecs>) -> AnonIterChain<Self::Item> {
-        ecs.archetypes.collect(indices)
+    unsafe fn collect<'w>(indices: &IndexSet<TableIndex>, id: ComponentId, ecs: Ptr<Ecs>) -> Result<AnonIterChain<'w, Self::Item>> {
+        ecs.as_ref().archetypes.collect(indices, id)
     }
 
-    fn accessors(accessors: &mut Vec<Accessor>) {
-        if *C::handle() == u16::MAX {
-            panic!("Tried to collect accessors for undeclared Component {} in a Query!", std::any::type_name::<C>());
-        }
+    fn accessors(accessors: &mut Vec<Accessor>, id: ComponentId) -> Result<()> {
+        accessors.push(Accessor::Mut(id));
 
-        accessors.push(Accessor::Mut(*C::handle()))
+        Ok(())
     }
 
-    fn wrap(data: &'static mut Self::Item) -> Self::Output {
-        Self(data)
+    fn wrap(data: &mut Self::Item) -> Self::Output<'_> {
+        data
     }
 }
 
-pub struct Query1<T1> 
+pub struct Query1<'w, T1> 
 where
     T1: QueryParam,
 {
-    t1: AnonIterChain<T1>,
+    t1: AnonIterChain<'w, T1::Item>,
     p: PackageIndexChain,
 }
 
-impl<T1> Iterator for Query1<T1>
+impl<'w, T1> Iterator for Query1<'w, T1>
 where
-    T1: QueryParam<Item=T1>,
+    T1: QueryParam,
 {
-    type Item = (T1::Output, PackageIndex);
+    type Item = (T1::Output<'w>, PackageIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.t1.iters.len() > 0 {
+        if !self.t1.iters.is_empty() {
             Some((
                 T1::wrap(self.t1.next().unwrap()),
                 self.p.next().unwrap(),
@@ -159,54 +194,53 @@ where
 
 impl<T1> IntoQuery for (T1,) 
 where
-    T1: QueryParam<Item=T1>,
+    T1: QueryParam,
 {
-    type Item = Query1<T1>;
+    type Item<'w> = Query1<'w, T1>;
 
-    fn into_query(ecs: Ptr<Ecs>) -> Trace<Self::Item> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
+    fn ids(components: &Components) -> Result<Vec<ComponentId>> {
+        Ok(vec![trace!(components.lookup::<T1::Item>())])
+    }
+
+    unsafe fn into_query<'w>(ecs: Ptr<Ecs>, ids: &[ComponentId]) -> Result<Self::Item<'w>> {
+        let arch = Archetype::of(ids);
 
         let indices = trace!(ecs.archetypes.query_cache(arch));
 
-        Trace::Ok(Query1 {
-            t1: T1::collect(indices, ecs.clone()),
+        Ok(Query1 {
+            t1: trace!(T1::collect(indices, ids[0], ecs.clone())),
             p: ecs.archetypes.collect_indices(indices),
         })
     }
 
-    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-
-        let mut ids = Vec::new();
-        ids.push(*T1::Item::handle());
+    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let ids = trace!(Self::ids(&ecs.components));
 
-        T1::accessors(a);
+        trace!(T1::accessors(a, ids[0]));
 
-        unsafe { (*ecs.get_mut()).archetypes.load_query(arch, ids) }
+        unsafe { (*ecs.get_mut()).archetypes.load_query(Archetype::of(&ids), ids) }
     }
 }
 
-pub struct Query2<T1, T2> 
+pub struct Query2<'w, T1, T2> 
 where
     T1: QueryParam,
     T2: QueryParam,
 {
-    t1: AnonIterChain<T1>,
-    t2: AnonIterChain<T2>,
+    t1: AnonIterChain<'w, T1::Item>,
+    t2: AnonIterChain<'w, T2::Item>,
     p: PackageIndexChain,
 }
 
-impl<T1, T2> Iterator for Query2<T1, T2>
+impl<'w, T1, T2> Iterator for Query2<'w, T1, T2>
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
+    T1: QueryParam,
+    T2: QueryParam,
 {
-    type Item = (T1::Output, T2::Output, PackageIndex);
+    type Item = (T1::Output<'w>, T2::Output<'w>, PackageIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.t1.iters.len() > 0 {
+        if !self.t1.iters.is_empty() {
             Some((
                 T1::wrap(self.t1.next().unwrap()),
                 T2::wrap(self.t2.next().unwrap()),
@@ -220,63 +254,59 @@ where
 
 impl<T1, T2> IntoQuery for (T1, T2) 
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
+    T1: QueryParam,
+    T2: QueryParam,
 {
-    type Item = Query2<T1, T2>;
+    type Item<'w> = Query2<'w, T1, T2>;
+
+    fn ids(components: &Components) -> Result<Vec<ComponentId>> {
+        Ok(vec![trace!(components.lookup::<T1::Item>()), trace!(components.lookup::<T2::Item>())])
+    }
 
-    fn into_query(ecs: Ptr<Ecs>) -> Trace<Self::Item> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
+    unsafe fn into_query<'w>(ecs: Ptr<Ecs>, ids: &[ComponentId]) -> Result<Self::Item<'w>> {
+        let arch = Archetype::of(ids);
 
         let indices = trace!(ecs.archetypes.query_cache(arch));
 
-        Trace::Ok(Query2 {
-            t1: T1::collect(indices, ecs.clone()),
-            t2: T2::collect(indices, ecs.clone()),
+        Ok(Query2 {
+            t1: trace!(T1::collect(indices, ids[0], ecs.clone())),
+            t2: trace!(T2::collect(indices, ids[1], ecs.clone())),
             p: ecs.archetypes.collect_indices(indices),
         })
     }
 
-    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
+    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let ids = trace!(Self::ids(&ecs.components));
 
-        let mut ids = Vec::new();
-        ids.push(*T1::Item::handle());
-        ids.push(*T2::Item::handle());
+        trace!(T1::accessors(a, ids[0]));
+        trace!(T2::accessors(a, ids[1]));
 
-        T1::accessors(a);
-        T2::accessors(a);
-
-        unsafe { (*ecs.get_mut()).archetypes.load_query(arch, ids) }
+        unsafe { (*ecs.get_mut()).archetypes.load_query(Archetype::of(&ids), ids) }
     }
 }
 
-pub struct Query3<T1, T2, T3> 
+pub struct Query3<'w, T1, T2, T3> 
 where
     T1: QueryParam,
     T2: QueryParam,
     T3: QueryParam,
 {
-    t1: AnonIterChain<T1>,
-    t2: AnonIterChain<T2>,
-    t3: AnonIterChain<T3>,
+    t1: AnonIterChain<'w, T1::Item>,
+    t2: AnonIterChain<'w, T2::Item>,
+    t3: AnonIterChain<'w, T3::Item>,
     p: PackageIndexChain,
 }
 
-impl<T1, T2, T3> Iterator for Query3<T1, T2, T3>
+impl<'w, T1, T2, T3> Iterator for Query3<'w, T1, T2, T3>
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
-    T3: QueryParam<Item=T3>,
+    T1: QueryParam,
+    T2: QueryParam,
+    T3: QueryParam,
 {
-    type Item = (T1::Output, T2::Output, T3::Output, PackageIndex);
+    type Item = (T1::Output<'w>, T2::Output<'w>, T3::Output<'w>, PackageIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.t1.iters.len() > 0 {
+        if !self.t1.iters.is_empty() {
             Some((
                 T1::wrap(self.t1.next().unwrap()),
                 T2::wrap(self.t2.next().unwrap()),
@@ -291,72 +321,65 @@ where
 
 impl<T1, T2, T3> IntoQuery for (T1, T2, T3) 
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
-    T3: QueryParam<Item=T3>,
+    T1: QueryParam,
+    T2: QueryParam,
+    T3: QueryParam,
 {
-    type Item = Query3<T1, T2, T3>;
+    type Item<'w> = Query3<'w, T1, T2, T3>;
 
-    fn into_query(ecs: Ptr<Ecs>) -> Trace<Self::Item> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
-        arch = arch.add(*T3::Item::handle() as u64);
+    fn ids(components: &Components) -> Result<Vec<ComponentId>> {
+        Ok(vec![trace!(components.lookup::<T1::Item>()), trace!(components.lookup::<T2::Item>()), trace!(components.lookup::<T3::Item>())])
+    }
+
+    unsafe fn into_query<'w>(ecs: Ptr<Ecs>, ids: &[ComponentId]) -> Result<Self::Item<'w>> {
+        let arch = Archetype::of(ids);
 
         let indices = trace!(ecs.archetypes.query_cache(arch));
 
-        Trace::Ok(Query3 {
-            t1: T1::collect(indices, ecs.clone()),
-            t2: T2::collect(indices, ecs.clone()),
-            t3: T3::collect(indices, ecs.clone()),
+        Ok(Query3 {
+            t1: trace!(T1::collect(indices, ids[0], ecs.clone())),
+            t2: trace!(T2::collect(indices, ids[1], ecs.clone())),
+            t3: trace!(T3::collect(indices, ids[2], ecs.clone())),
             p: ecs.archetypes.collect_indices(indices),
         })
     }
 
-    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
-        arch = arch.add(*T3::Item::handle() as u64);
+    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let ids = trace!(Self::ids(&ecs.components));
 
-        let mut ids = Vec::new();
-        ids.push(*T1::Item::handle());
-        ids.push(*T2::Item::handle());
-        ids.push(*T3::Item::handle());
+        trace!(T1::accessors(a, ids[0]));
+        trace!(T2::accessors(a, ids[1]));
+        trace!(T3::accessors(a, ids[2]));
 
-        T1::accessors(a);
-        T2::accessors(a);
-        T3::accessors(a);
-
-        unsafe { (*ecs.get_mut()).archetypes.load_query(arch, ids) }
+        unsafe { (*ecs.get_mut()).archetypes.load_query(Archetype::of(&ids), ids) }
     }
 }
 
-pub struct Query4<T1, T2, T3, T4> 
+pub struct Query4<'w, T1, T2, T3, T4> 
 where
     T1: QueryParam,
     T2: QueryParam,
     T3: QueryParam,
     T4: QueryParam,
 {
-    t1: AnonIterChain<T1>,
-    t2: AnonIterChain<T2>,
-    t3: AnonIterChain<T3>,
-    t4: AnonIterChain<T4>,
+    t1: AnonIterChain<'w, T1::Item>,
+    t2: AnonIterChain<'w, T2::Item>,
+    t3: AnonIterChain<'w, T3::Item>,
+    t4: AnonIterChain<'w, T4::Item>,
     p: PackageIndexChain,
 }
 
-impl<T1, T2, T3, T4> Iterator for Query4<T1, T2, T3, T4>
+impl<'w, T1, T2, T3, T4> Iterator for Query4<'w, T1, T2, T3, T4>
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
-    T3: QueryParam<Item=T3>,
-    T4: QueryParam<Item=T4>,
+    T1: QueryParam,
+    T2: QueryParam,
+    T3: QueryParam,
+    T4: QueryParam,
 {
-    type Item = (T1::Output, T2::Output, T3::Output, T4::Output, PackageIndex);
+    type Item = (T1::Output<'w>, T2::Output<'w>, T3::Output<'w>, T4::Output<'w>, PackageIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.t1.iters.len() > 0 {
+        if !self.t1.iters.is_empty() {
             Some((
                 T1::wrap(self.t1.next().unwrap()),
                 T2::wrap(self.t2.next().unwrap()),
@@ -372,49 +395,39 @@ where
 
 impl<T1, T2, T3, T4> IntoQuery for (T1, T2, T3, T4) 
 where
-    T1: QueryParam<Item=T1>,
-    T2: QueryParam<Item=T2>,
-    T3: QueryParam<Item=T3>,
-    T4: QueryParam<Item=T4>,
+    T1: QueryParam,
+    T2: QueryParam,
+    T3: QueryParam,
+    T4: QueryParam,
 {
-    type Item = Query4<T1, T2, T3, T4>;
+    type Item<'w> = Query4<'w, T1, T2, T3, T4>;
 
-    fn into_query(ecs: Ptr<Ecs>) -> Trace<Self::Item> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
-        arch = arch.add(*T3::Item::handle() as u64);
-        arch = arch.add(*T4::Item::handle() as u64);
+    fn ids(components: &Components) -> Result<Vec<ComponentId>> {
+        Ok(vec![trace!(components.lookup::<T1::Item>()), trace!(components.lookup::<T2::Item>()), trace!(components.lookup::<T3::Item>()), trace!(components.lookup::<T4::Item>())])
+    }
+
+    unsafe fn into_query<'w>(ecs: Ptr<Ecs>, ids: &[ComponentId]) -> Result<Self::Item<'w>> {
+        let arch = Archetype::of(ids);
 
         let indices = trace!(ecs.archetypes.query_cache(arch));
 
-        Trace::Ok(Query4 {
-            t1: T1::collect(indices, ecs.clone()),
-            t2: T2::collect(indices, ecs.clone()),
-            t3: T3::collect(indices, ecs.clone()),
-            t4: T4::collect(indices, ecs.clone()),
+        Ok(Query4 {
+            t1: trace!(T1::collect(indices, ids[0], ecs.clone())),
+            t2: trace!(T2::collect(indices, ids[1], ecs.clone())),
+            t3: trace!(T3::collect(indices, ids[2], ecs.clone())),
+            t4: trace!(T4::collect(indices, ids[3], ecs.clone())),
             p: ecs.archetypes.collect_indices(indices),
         })
     }
 
-    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> {
-        let mut arch = Archetype::new();
-        arch = arch.add(*T1::Item::handle() as u64);
-        arch = arch.add(*T2::Item::handle() as u64);
-        arch = arch.add(*T3::Item::handle() as u64);
-        arch = arch.add(*T4::Item::handle() as u64);
-
-        let mut ids = Vec::new();
-        ids.push(*T1::Item::handle());
-        ids.push(*T2::Item::handle());
-        ids.push(*T3::Item::handle());
-        ids.push(*T4::Item::handle());
-
-        T1::accessors(a);
-        T2::accessors(a);
-        T3::accessors(a);
-        T4::accessors(a);
-
-        unsafe { (*ecs.get_mut()).archetypes.load_query(arch, ids) }
+    fn accessors(a: &mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> {
+        let ids = trace!(Self::ids(&ecs.components));
+
+        trace!(T1::accessors(a, ids[0]));
+        trace!(T2::accessors(a, ids[1]));
+        trace!(T3::accessors(a, ids[2]));
+        trace!(T4::accessors(a, ids[3]));
+
+        unsafe { (*ecs.get_mut()).archetypes.load_query(Archetype::of(&ids), ids) }
     }
 }
\ No newline at end of file
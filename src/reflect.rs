@@ -0,0 +1,202 @@
+
+use std::any::{Any, TypeId, type_name};
+
+use super::anon::ComponentInfo;
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
+
+/// A type whose fields can be listed, read and written by name at runtime.
+///
+/// Derive it with `#[derive(Reflect)]` on a struct whose fields are all `Reflect`,
+/// and add `#[component(reflect)]` to a component to expose its fields through
+/// the [`ComponentInfo`](super::anon::ComponentInfo) it is registered with.
+pub trait Reflect: Any {
+    fn type_info() -> TypeInfo;
+}
+
+/// The name and fields of a [`Reflect`] type. Types without fields, such as
+/// numbers and strings, are the leaves that paths end in.
+///
+/// Fields are read and written through their offsets, so only `#[derive(Reflect)]`
+/// and other unsafe code can describe a type with fields.
+#[derive(Clone, Debug)]
+pub struct TypeInfo {
+    name: &'static str,
+    type_id: TypeId,
+    fields: Vec<FieldInfo>,
+}
+
+/// A field of a [`Reflect`] type.
+#[derive(Copy, Clone, Debug)]
+pub struct FieldInfo {
+    /// The name of the field, or its position for tuple structs.
+    name: &'static str,
+    type_name: &'static str,
+    type_id: TypeId,
+    /// How many bytes into the value the field starts.
+    offset: usize,
+    /// The fields of the field's own type.
+    info: fn() -> TypeInfo,
+}
+
+impl TypeInfo {
+    /// Describes a type without fields.
+    pub fn leaf<T: Any>() -> Self {
+        Self {
+            name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Describes `T` with `fields`.
+    ///
+    /// # Safety
+    /// Every one of `fields` must describe a field of `T`.
+    pub unsafe fn new<T: Any>(fields: Vec<FieldInfo>) -> Self {
+        Self {
+            name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            fields,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Follows a path of field names separated by dots, such as `"velocity.x"`,
+    /// returning the last field and how many bytes into the value it starts.
+    pub fn resolve(&self, path: &str) -> Option<(usize, FieldInfo)> {
+        let mut names = path.split('.');
+
+        let mut field = *self.field(names.next()?)?;
+        let mut offset = field.offset;
+
+        for name in names {
+            field = *field.info()?.field(name)?;
+            offset += field.offset;
+        }
+
+        Some((offset, field))
+    }
+
+    /// Every path that ends in a type without fields, in declaration order.
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+
+        for field in self.fields.iter() {
+            let Some(info) = field.info() else {
+                continue
+            };
+
+            if info.fields.is_empty() {
+                paths.push(field.name.to_string());
+            } else {
+                paths.extend(info.paths().into_iter().map(|path| format!("{}.{}", field.name, path)));
+            }
+        }
+
+        paths
+    }
+}
+
+impl FieldInfo {
+    /// Describes a field of type `F`, called `name`.
+    ///
+    /// # Safety
+    /// The field has to start `offset` bytes into the type it is a field of.
+    pub unsafe fn new<F: Reflect>(name: &'static str, offset: usize) -> Self {
+        Self {
+            name,
+            type_name: type_name::<F>(),
+            type_id: TypeId::of::<F>(),
+            offset,
+            info: F::type_info,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The fields of the field's own type, or `None` if its `Reflect` impl
+    /// describes some other type.
+    pub fn info(&self) -> Option<TypeInfo> {
+        Some((self.info)()).filter(|info| info.type_id == self.type_id)
+    }
+}
+
+/// Errors unless `reflect` describes the type of the component `info`,
+/// since its fields are found by their offsets into that type. Components
+/// without a Rust type can't be reflected, as nothing says what they hold.
+pub(crate) fn check(info: &ComponentInfo, reflect: &TypeInfo) -> Result<()> {
+    if info.type_id != Some(reflect.type_id) {
+        start_trace!(EcsError::ReflectMismatch { component: info.name, reflected: reflect.name })
+    }
+
+    Ok(())
+}
+
+/// The field at `path` of the value of the reflected component `info` behind `ptr`,
+/// as long as it is a `T`.
+///
+/// # Safety
+/// `ptr` must point to a valid value of the component `info`.
+pub(crate) unsafe fn field_ptr<T: Reflect>(ptr: *mut u8, info: &ComponentInfo, path: &str) -> Result<*mut T> {
+    let Some(reflect) = info.reflect.map(|reflect| reflect()) else {
+        start_trace!(EcsError::NotReflected(info.name))
+    };
+
+    trace!(check(info, &reflect));
+
+    let Some((offset, field)) = reflect.resolve(path) else {
+        start_trace!(EcsError::FieldNotFound { component: info.name, path: path.to_string() })
+    };
+
+    if field.type_id != TypeId::of::<T>() {
+        start_trace!(EcsError::FieldMismatch { path: path.to_string(), expected: type_name::<T>(), found: field.type_name })
+    }
+
+    Ok(ptr.add(offset).cast::<T>())
+}
+
+macro_rules! impl_reflect_leaf {
+    ($($ty:ty),*) => {
+        $(
+            impl Reflect for $ty {
+                fn type_info() -> TypeInfo {
+                    TypeInfo::leaf::<$ty>()
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_leaf!(bool, char, String, f32, f64);
+impl_reflect_leaf!(i8, i16, i32, i64, i128, isize);
+impl_reflect_leaf!(u8, u16, u32, u64, u128, usize);
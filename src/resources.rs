@@ -1,49 +1,242 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::thread::{self, ThreadId};
 
-use std::any::Any;
-
-use super::handle::Resource;
+use super::handle::{Handle, Resource};
 use super::ptr::Unsafe;
-use super::error::Trace;
-use crate::start_trace;
-use super::params::{ResMut, ResRef};
+use super::error::{EcsError, Result};
+use crate::{start_trace, trace};
+use super::params::{NonSend, NonSendMut, ResMut, ResRef};
+
+/// When a resource was inserted, and when it was last mutably dereferenced,
+/// as change ticks of the Ecs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeTicks {
+    pub added: u64,
+    pub changed: u64,
+}
+
+/// A resource along with its change ticks.
+struct Slot<R> {
+    value: R,
+    ticks: ChangeTicks,
+}
 
 pub struct Resources {
-    resources: Vec<Box<dyn Any>>,
+    /// Removed resources leave an empty slot, so the handles of the others stay the same.
+    resources: Vec<Option<Box<dyn Any>>>,
+    names: Vec<&'static str>,
+    /// The thread every non-send resource was inserted on, and the only one it can be used on.
+    owners: Vec<Option<ThreadId>>,
+    /// The handle of every resource type that has a slot.
+    handles: HashMap<TypeId, u16>,
 }
 
+// `Resource`s are `Send + Sync`, and every access to a non-send one checks that it
+// happens on the thread that owns it. Resources stay `!Send`, so non-send ones are
+// dropped on their own thread too.
+unsafe impl Sync for Resources {}
+
 impl Resources {
     pub fn new() -> Self {
         Self {
             resources: Vec::new(),
+            names: Vec::new(),
+            owners: Vec::new(),
+            handles: HashMap::new(),
         }
     }
 
-    pub fn add_resource<R: Resource>(&mut self, resource: R) {
-        *R::handle() = self.resources.len() as u16;
-        self.resources.push(Box::new(Unsafe::new(resource)));
+    /// The type name of the resource behind `handle`.
+    pub fn name(&self, handle: u16) -> Option<&'static str> {
+        self.names.get(handle as usize).copied()
+    }
+
+    pub fn add_resource<R: Resource>(&mut self, resource: R, tick: u64) {
+        self.insert_resource(resource, tick);
+    }
+
+    /// Inserts `resource` as added and changed at `tick`, returning the one it replaced if there was one.
+    pub fn insert_resource<R: Resource>(&mut self, resource: R, tick: u64) -> Option<R> {
+        self.insert(resource, tick, None)
+    }
+
+    /// Inserts a resource that can only be used on the current thread, through 
+    /// [`NonSend`](super::params::NonSend) and [`NonSendMut`](super::params::NonSendMut).
+    /// Fails if it would replace one that belongs to another thread.
+    pub fn insert_non_send<R: 'static>(&mut self, resource: R, tick: u64) -> Result<Option<R>> {
+        trace!(self.check_owner::<R>());
+        Ok(self.insert(resource, tick, Some(thread::current().id())))
     }
 
-    pub fn get_resource_ref<R: Resource>(&self) -> Trace<ResRef<R>> {
-        if *R::handle() >= self.resources.len() as u16 {
-            start_trace!(format!("Handle for resource {} has not been initialized! Did you forget to declare it?", R::name()));
+    fn insert<R: Handle>(&mut self, resource: R, tick: u64, owner: Option<ThreadId>) -> Option<R> {
+        let handle = self.register::<R>() as usize;
+        let slot = Slot { value: resource, ticks: ChangeTicks { added: tick, changed: tick } };
+        let old = self.resources[handle].replace(Box::new(Unsafe::new(slot)));
+        self.owners[handle] = owner;
+
+        old.and_then(|old| old.downcast::<Unsafe<Slot<R>>>().ok()).map(|old| old.into_inner().value)
+    }
+
+    /// Inserts the default value of `R` at `tick`, unless there already is one.
+    pub fn init_resource<R: Resource + Default>(&mut self, tick: u64) {
+        if !self.contains_resource::<R>() {
+            self.insert_resource(R::default(), tick);
         }
+    }
 
-        if let Some(res) = self.resources[*R::handle() as usize].downcast_ref::<Unsafe<R>>() {
-            Trace::Ok(ResRef(res.get()))
-        } else {
-            start_trace!(format!("Handle for resource {} was invalid!", R::name()));
+    /// Removes the resource and hands it back, if there was one.
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.remove::<R>()
+    }
+
+    /// Removes the non-send resource and hands it back, if there was one.
+    /// Fails on any thread but the one it was inserted on.
+    pub fn remove_non_send<R: 'static>(&mut self) -> Result<Option<R>> {
+        trace!(self.check_owner::<R>());
+        Ok(self.remove::<R>())
+    }
+
+    /// Errors if the resource in the slot of `R` belongs to another thread, as it 
+    /// can't be moved out or dropped anywhere else. Resources that are `Send`
+    /// skip this, they can be moved to any thread.
+    fn check_owner<R: Handle>(&self) -> Result<()> {
+        let Some(handle) = self.handle::<R>() else {
+            return Ok(());
+        };
+
+        let occupied = self.resources[handle as usize].is_some();
+        if occupied && self.owners[handle as usize].is_some_and(|owner| owner != thread::current().id()) {
+            start_trace!(EcsError::WrongThread(R::name()));
         }
+
+        Ok(())
     }
 
-    pub fn get_resource_mut<R: Resource>(&self) -> Trace<ResMut<R>> {
-        if *R::handle() >= self.resources.len() as u16 {
-            start_trace!(format!("Handle for resource {} has not been initialized! Did you forget to declare it?", R::name()));
+    fn remove<R: Handle>(&mut self) -> Option<R> {
+        let handle = self.handle::<R>()?;
+
+        self.resources[handle as usize].take()
+            .and_then(|old| old.downcast::<Unsafe<Slot<R>>>().ok())
+            .map(|old| old.into_inner().value)
+    }
+
+    pub fn contains_resource<R: Resource>(&self) -> bool {
+        self.contains::<R>()
+    }
+
+    pub fn contains_non_send<R: 'static>(&self) -> bool {
+        self.contains::<R>()
+    }
+
+    pub(crate) fn contains<R: Handle>(&self) -> bool {
+        self.handle::<R>().is_some_and(|handle| self.resources[handle as usize].is_some())
+    }
+
+    /// The handle of `R`, reserving an empty slot for it if it was never inserted,
+    /// so systems that might use it can be scheduled before it exists.
+    pub(crate) fn register<R: Handle>(&mut self) -> u16 {
+        if let Some(handle) = self.handle::<R>() {
+            return handle;
         }
 
-        if let Some(res) = self.resources[*R::handle() as usize].downcast_ref::<Unsafe<R>>() {
-            Trace::Ok(ResMut(res.get()))
+        let handle = self.resources.len() as u16;
+        self.handles.insert(TypeId::of::<R>(), handle);
+        self.resources.push(None);
+        self.names.push(R::name());
+        self.owners.push(None);
+
+        handle
+    }
+
+    /// The handle of `R`, if it has a slot in these resources.
+    pub(crate) fn handle<R: Handle>(&self) -> Option<u16> {
+        self.handles.get(&TypeId::of::<R>()).copied()
+    }
+
+    /// When the resource was inserted and last changed, if it is there.
+    pub fn ticks<R: Resource>(&self) -> Option<ChangeTicks> {
+        self.get::<R>().ok().map(|slot| unsafe { (*slot).ticks })
+    }
+
+    fn get<R: Handle>(&self) -> Result<*mut Slot<R>> {
+        let Some(handle) = self.handle::<R>() else {
+            start_trace!(EcsError::MissingResource(R::name()));
+        };
+
+        let Some(res) = self.resources[handle as usize].as_ref() else {
+            start_trace!(EcsError::MissingResource(R::name()));
+        };
+
+        if self.owners[handle as usize].is_some_and(|owner| owner != thread::current().id()) {
+            start_trace!(EcsError::WrongThread(R::name()));
+        }
+
+        if let Some(res) = res.downcast_ref::<Unsafe<Slot<R>>>() {
+            Ok(res.get())
         } else {
-            start_trace!(format!("Handle for resource {} was invalid!", R::name()));
+            start_trace!(EcsError::Internal("resource handle pointed at a resource of a different type"));
+        }
+    }
+
+    /// The resource, with changes after `last_run` counting as new.
+    pub fn get_resource_ref<R: Resource>(&self, last_run: u64) -> Result<ResRef<'_, R>> {
+        let slot = trace!(self.get::<R>());
+
+        unsafe {
+            Ok(ResRef { value: &(*slot).value, ticks: &(*slot).ticks, last_run, marker: PhantomData })
+        }
+    }
+
+    /// The resource, with changes after `last_run` counting as new,
+    /// and changes made through it stamped with `this_run`. Nothing else may
+    /// borrow the resource while it is, which the scheduler's access checks ensure.
+    pub(crate) fn get_resource_mut<R: Resource>(&self, last_run: u64, this_run: u64) -> Result<ResMut<'_, R>> {
+        let slot = trace!(self.get::<R>());
+
+        unsafe {
+            Ok(ResMut { value: &mut (*slot).value, ticks: &mut (*slot).ticks, last_run, this_run, marker: PhantomData })
+        }
+    }
+
+    /// The non-send resource, as long as this is the thread it was inserted on.
+    pub fn get_non_send_ref<R: 'static>(&self, last_run: u64) -> Result<NonSend<'_, R>> {
+        let slot = trace!(self.get::<R>());
+
+        unsafe {
+            Ok(NonSend(ResRef { value: &(*slot).value, ticks: &(*slot).ticks, last_run, marker: PhantomData }))
         }
     }
-}
\ No newline at end of file
+
+    /// The non-send resource, as long as this is the thread it was inserted on.
+    /// Nothing else may borrow the resource while it is, as with [`get_resource_mut`](Self::get_resource_mut).
+    pub(crate) fn get_non_send_mut<R: 'static>(&self, last_run: u64, this_run: u64) -> Result<NonSendMut<'_, R>> {
+        let slot = trace!(self.get::<R>());
+
+        unsafe {
+            Ok(NonSendMut(ResMut { value: &mut (*slot).value, ticks: &mut (*slot).ticks, last_run, this_run, marker: PhantomData }))
+        }
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the system when `R` was inserted or changed since it last ran. Use with `run_if`.
+pub fn resource_changed<R: Resource>(resource: Option<ResRef<R>>) -> bool {
+    resource.is_some_and(|resource| resource.is_changed())
+}
+
+/// Runs the system when `R` was inserted since it last ran. Use with `run_if`.
+pub fn resource_added<R: Resource>(resource: Option<ResRef<R>>) -> bool {
+    resource.is_some_and(|resource| resource.is_added())
+}
+
+/// Runs the system while there is an `R`. Use with `run_if`.
+pub fn resource_exists<R: Resource>(resource: Option<ResRef<R>>) -> bool {
+    resource.is_some()
+}
@@ -1,48 +1,258 @@
 
-use std::any::type_name;
+use std::any::Any;
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use rayon::prelude::*;
 
-use super::error::Trace;
-use super::params::System;
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
+use super::params::{BoxedSystem, Locals, Order, RunCondition, SystemFn};
+use super::introspect::{Access, ConflictInfo, GroupInfo, StageInfo, SystemInfo};
 use super::ptr::Ptr;
 use super::ecs::Ecs;
-use super::params::Fetch;
-use super::trace;
 
 pub struct Scheduler {
     stage: &'static str,
     temp: Vec<Node>,
     groups: Vec<Group>,
+    ambiguities: Vec<Ambiguity>,
+    /// For the dynamic executor, how many systems each system waits for,
+    /// and which systems wait for it, in group order.
+    dependencies: Vec<usize>,
+    dependents: Vec<Vec<usize>>,
+    /// Whether the groups are up to date with the systems of the stage.
+    finalized: bool,
 }
 
+/// Two systems that conflict with no order between them, whether either is exclusive,
+/// and the accessors that conflict.
+type Ambiguity = (&'static str, &'static str, bool, Vec<(Accessor, Accessor)>);
+
 impl Scheduler {
     pub fn new(stage: &'static str) -> Self {
         Self {
             stage,
             temp: Vec::new(),
             groups: Vec::new(),
+            ambiguities: Vec::new(),
+            dependencies: Vec::new(),
+            dependents: Vec::new(),
+            finalized: false,
         }
     }
 
-    pub fn add_system<S: System + Fetch>(&mut self) {
+    /// Adds `system` to the stage. Systems are told apart by name, so it fails 
+    /// if a system with the same name is already in the stage.
+    pub fn add_system(&mut self, system: BoxedSystem) -> Result<()> {
+        if self.is_enabled(system.name).is_some() {
+            start_trace!(EcsError::DuplicateSystem { stage: self.stage, system: system.name });
+        }
+
         self.temp.push(
             Node {
-                execute: |ecs| { S::execute(trace!(S::fetch(ecs))) },
-                access: |accessors, ecs| { S::access(accessors, ecs) },
+                execute: system.execute,
+                locals: Locals::new(),
+                access: system.access,
                 accessors: Vec::new(),
                 edges: Vec::new(),
-                name: type_name::<S>(),
+                name: system.name,
+                order: system.order,
+                conditions: system.conditions,
+                exclusive: system.exclusive,
+                main_thread: system.exclusive,
+                enabled: true,
+                sample: None,
             }
         );
+
+        self.finalized = false;
+        Ok(())
+    }
+
+    /// Removes the system called `name`, returning whether it was in the stage.
+    /// The stage is planned again the next time it is finalized.
+    pub fn remove_system(&mut self, name: &str) -> bool {
+        let before = self.len();
+
+        self.temp.retain(|node| node.name != name);
+        for group in self.groups.iter_mut() {
+            group.systems.retain(|node| node.name != name);
+        }
+
+        if self.len() == before {
+            return false;
+        }
+
+        self.groups.retain(|group| !group.systems.is_empty());
+        self.finalized = false;
+        true
+    }
+
+    /// Sets whether the system called `name` runs, returning whether it was in the stage.
+    /// A disabled system keeps its place in its group, so nothing has to be planned again.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+
+        for node in self.nodes_mut().filter(|node| node.name == name) {
+            node.enabled = enabled;
+            found = true;
+        }
+
+        found
+    }
+
+    /// Whether the system called `name` runs, or `None` if it isn't in the stage.
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self.temp.iter()
+            .chain(self.groups.iter().flat_map(|group| group.systems.iter()))
+            .find(|node| node.name == name)
+            .map(|node| node.enabled)
+    }
+
+    /// Whether systems were added or removed since the stage was last finalized.
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
+    }
+
+    /// How many systems are in the stage, grouped or not.
+    pub fn len(&self) -> usize {
+        self.temp.len() + self.groups.iter().map(|group| group.systems.len()).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn nodes_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.temp.iter_mut().chain(self.groups.iter_mut().flat_map(|group| group.systems.iter_mut()))
+    }
+
+    /// The name the stage was added with.
+    pub(crate) fn name(&self) -> &'static str {
+        self.stage
     }
 
-    pub fn finalize(&mut self, ecs: Ptr<Ecs>) {
+    /// What every system that ran during the last profiled `execute` measured.
+    pub(crate) fn samples(&self) -> Vec<SystemSample> {
+        self.groups.iter()
+            .enumerate()
+            .flat_map(|(group, g)| g.systems.iter().map(move |node| (group, node)))
+            .filter_map(|(group, node)| node.sample.map(|sample| SystemSample {
+                group,
+                name: node.name,
+                start: sample.start,
+                duration: sample.duration,
+                thread: sample.thread,
+                entities: node.locals.entities,
+                commands: node.locals.commands,
+            }))
+            .collect()
+    }
+
+    /// Describes the systems of this stage, and how they were grouped by the last `finalize`.
+    pub(crate) fn info(&self, name_of: &dyn Fn(Accessor) -> &'static str) -> StageInfo {
+        let system = |node: &Node| SystemInfo {
+            name: node.name,
+            access: node.accessors.iter().map(|accessor| Access {
+                accessor: *accessor,
+                name: name_of(*accessor),
+            }).collect(),
+            exclusive: node.exclusive,
+            enabled: node.enabled,
+        };
+
+        let groups: Vec<GroupInfo> = self.groups.iter().map(|group| GroupInfo {
+            systems: group.systems.iter().map(system).collect(),
+        }).collect();
+
+        // Every pair of systems that ended up in different groups because of each other.
+        let mut conflicts = Vec::new();
+        let nodes: Vec<(usize, &Node)> = self.groups.iter()
+            .enumerate()
+            .flat_map(|(i, group)| group.systems.iter().map(move |node| (i, node)))
+            .collect();
+
+        for (i, (group_a, a)) in nodes.iter().enumerate() {
+            for (group_b, b) in nodes[i + 1..].iter() {
+                if group_a == group_b {
+                    continue;
+                }
+
+                let reasons: Vec<(Access, Access)> = conflicts_between(&a.accessors, &b.accessors)
+                    .into_iter()
+                    .map(|(x, y)| (
+                        Access { accessor: x, name: name_of(x) },
+                        Access { accessor: y, name: name_of(y) },
+                    ))
+                    .collect();
+
+                let exclusive = a.exclusive || b.exclusive;
+                if exclusive || !reasons.is_empty() {
+                    conflicts.push(ConflictInfo { a: a.name, b: b.name, exclusive, reasons });
+                }
+            }
+        }
+
+        let ambiguities = self.ambiguities.iter().map(|(a, b, exclusive, reasons)| ConflictInfo {
+            a, 
+            b, 
+            exclusive: *exclusive,
+            reasons: reasons.iter().map(|(x, y)| (
+                Access { accessor: *x, name: name_of(*x) },
+                Access { accessor: *y, name: name_of(*y) },
+            )).collect(),
+        }).collect();
+
+        StageInfo {
+            name: self.stage,
+            groups,
+            pending: self.temp.iter().map(system).collect(),
+            conflicts,
+            ambiguities,
+        }
+    }
+
+    /// Groups the systems of the stage so that systems in the same group never conflict,
+    /// and every system runs in a later group than the systems ordered before it.
+    /// Conflicting systems with no order between them are handled as `check` says.
+    pub fn finalize(&mut self, ecs: Ptr<Ecs>, check: AmbiguityCheck) -> Result<()> {
+        // Plan the whole stage again, including systems that were grouped before.
+        for group in self.groups.drain(..) {
+            self.temp.extend(group.systems);
+        }
+
         // Compute Queries & Accessors for Each System
         for node in self.temp.iter_mut() {
-            if let Trace::Err(e) = (node.access)(&mut node.accessors, ecs.clone()) {
-                panic!("System Scheduler for stage {} encountered error in system {} with trace: \n {}", self.stage, node.name, e);
+            node.accessors.clear();
+            node.edges.clear();
+            trace!((node.access)(&mut node.accessors, ecs.clone()));
+
+            // A system that conflicts with itself would be handed aliasing references.
+            for (i, accessor) in node.accessors.iter().enumerate() {
+                if let Some((a, b)) = conflicts_between(&[*accessor], &node.accessors[i + 1..]).first() {
+                    let describe = |accessor: Accessor| Access { accessor, name: ecs.accessor_name(accessor) }.to_string();
+
+                    start_trace!(EcsError::AccessConflict { 
+                        system: node.name, 
+                        a: describe(*a), 
+                        b: describe(*b),
+                    })
+                }
             }
+
+            // conditions run before the system rather than alongside it, so they may
+            // read what it writes, but not while other systems in its group write it.
+            for condition in node.conditions.iter() {
+                trace!((condition.access)(&mut node.accessors, ecs.clone()));
+            }
+
+            node.main_thread = node.exclusive || node.accessors.contains(&Accessor::MainThread);
         }
 
         // Compute all compatible systems.
@@ -60,71 +270,395 @@ impl Scheduler {
 
         // Sort the systems in order from most to least compatability
         self.temp.sort_by(|a, b| {
-            b.edges.len().partial_cmp(&a.edges.len()).unwrap()
+            b.edges.len().cmp(&a.edges.len())
         });
 
-        // for every node... (in reverse order of compatability)
-        'l1: while let Some(node) = self.temp.pop() {
-            // for every group...
-            'l2: for group in self.groups.iter_mut() {
-                // ...check if the node is compatable.
-                for system in group.systems.iter() {
-                    // if its not, go to the next group.
-                    if conflicts(system, &node) {
-                        continue 'l2;
-                    }
+        let before = self.ordering();
+        trace!(self.find_ambiguities(&before, check));
+
+        // for every node... (in reverse order of compatability, 
+        // once everything ordered before it has been placed)
+        let mut nodes: Vec<Option<Node>> = self.temp.drain(..).map(Some).collect();
+        let mut placed: Vec<Option<usize>> = vec![None; nodes.len()];
+
+        for _ in 0..nodes.len() {
+            let ready = (0..nodes.len()).rev().find(|i| {
+                nodes[*i].is_some() && before[*i].iter().all(|pred| placed[*pred].is_some())
+            });
+
+            let Some(i) = ready else {
+                // everything left is waiting on something else that is left.
+                let name = nodes.iter().flatten().next().map_or("unknown", |node| node.name);
+                start_trace!(EcsError::OrderingCycle(name))
+            };
+
+            let node = nodes[i].take().unwrap();
+            let first = before[i].iter().filter_map(|pred| placed[*pred]).map(|g| g + 1).max().unwrap_or(0);
+
+            // ...find the first compatible group it's allowed in...
+            let group = self.groups[first..].iter()
+                .position(|group| group.systems.iter().all(|system| !conflicts(system, &node)))
+                .map(|g| g + first);
+
+            match group {
+                Some(g) => {
+                    self.groups[g].systems.push(node);
+                    placed[i] = Some(g);
+                },
+                None => {
+                    // ...and if none is found, push a new group.
+                    // `first` is at most the number of groups, so it comes after them all.
+                    self.groups.push(Group {
+                        systems: vec![node]
+                    });
+                    placed[i] = Some(self.groups.len() - 1);
                 }
+            }
+        }
 
-                // if the node is compatible
-                // push the node to the group.
-                group.systems.push(node);
-                continue 'l1;
+        self.plan_dependencies();
+        self.finalized = true;
+
+        Ok(())
+    }
+
+    /// For every system in `temp`, the indices of the systems that must run before it.
+    fn ordering(&self) -> Vec<Vec<usize>> {
+        let mut before = vec![Vec::new(); self.temp.len()];
+
+        for (i, node) in self.temp.iter().enumerate() {
+            for (j, other) in self.temp.iter().enumerate() {
+                if node.order.after.contains(&other.name) || other.order.before.contains(&node.name) {
+                    before[i].push(j);
+                }
             }
+        }
+
+        before
+    }
+
+    /// Records every pair of conflicting systems in `temp` with no order between them.
+    fn find_ambiguities(&mut self, before: &[Vec<usize>], check: AmbiguityCheck) -> Result<()> {
+        self.ambiguities.clear();
+
+        for i in 0..self.temp.len() {
+            for j in (i + 1)..self.temp.len() {
+                let (a, b) = (&self.temp[i], &self.temp[j]);
+
+                if a.order.ambiguous_with.contains(&b.name) || b.order.ambiguous_with.contains(&a.name) {
+                    continue;
+                }
+
+                if runs_before(before, i, j) || runs_before(before, j, i) {
+                    continue;
+                }
 
-            // if no compatible group is found, push a new group.
-            self.groups.push(Group {
-                systems: vec![node]
-            })
+                // an exclusive system conflicts with every other system, whatever they access.
+                let exclusive = a.exclusive || b.exclusive;
+                let reasons = conflicts_between(&a.accessors, &b.accessors);
+                if exclusive || !reasons.is_empty() {
+                    self.ambiguities.push((a.name, b.name, exclusive, reasons));
+                }
+            }
+        }
+
+        match check {
+            AmbiguityCheck::Ignore => {},
+            AmbiguityCheck::Warn => {
+                for (a, b, _, _) in self.ambiguities.iter() {
+                    eprintln!("{}", EcsError::Ambiguous { stage: self.stage, a, b });
+                }
+            },
+            AmbiguityCheck::Deny => {
+                if let Some((a, b, _, _)) = self.ambiguities.first() {
+                    start_trace!(EcsError::Ambiguous { stage: self.stage, a, b })
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Runs every enabled system in the stage with `executor`. Systems that fail are 
+    /// reported and handled as `recovery` says, the rest of the stage still runs. 
+    /// With `profile`, every system records how long it took and what it touched.
+    pub fn execute(&mut self, ecs: Ptr<Ecs>, recovery: Recovery, executor: Executor, profile: bool) {
+        let run = Run { stage: self.stage, recovery, profile };
+
+        match executor {
+            Executor::Grouped => self.execute_grouped(ecs, run),
+            Executor::MultiThreaded => self.execute_dynamic(ecs, run),
+            Executor::SingleThreaded => {
+                for node in self.groups.iter_mut().flat_map(|group| group.systems.iter_mut()) {
+                    node.run(ecs.clone(), run);
+                }
+            },
         }
     }
 
-    pub fn execute(&self, ecs: Ptr<Ecs>) {
+    /// Runs the groups one after another, with the systems of each group in parallel.
+    fn execute_grouped(&mut self, ecs: Ptr<Ecs>, run: Run) {
+
         // Execute the groups
-        for group in self.groups.iter() {
+        for group in self.groups.iter_mut() {
+            if group.systems.len() > 1 && group.systems.iter().any(|node| node.main_thread) {
+                // the body of an in place scope runs on this thread, the spawned systems don't.
+                let (main, others): (Vec<&mut Node>, Vec<&mut Node>) = group.systems.iter_mut()
+                    .partition(|node| node.main_thread);
+
+                rayon::in_place_scope(|scope| {
+                    for node in others {
+                        let ecs = ecs.clone();
+                        scope.spawn(move |_| node.run(ecs, run));
+                    }
+
+                    for node in main {
+                        node.run(ecs.clone(), run);
+                    }
+                });
+
+                continue;
+            }
+
             match group.systems.len() {
                 1 => {
                     // Execute on main thread, foregoing
                     // any overhead from launching threads.
-                    if let Trace::Err(e) = (group.systems[0].execute)(ecs.clone()) {
-                        panic!("System Scheduler for stage {} encountered error in system {} with trace: \n {}"
-                        , self.stage,group.systems[0].name, e);
-                    }
+                    group.systems[0].run(ecs.clone(), run);
                 },
                 2 => {
                     // use join if there are only 2 systems
+                    let (a, b) = group.systems.split_at_mut(1);
                     rayon::join(
-                        || if let Trace::Err(e) = (group.systems[0].execute)(ecs.clone()) {
-                            panic!("System Scheduler for stage {} encountered error in system {} with trace: \n {}"
-                            , self.stage, group.systems[0].name, e);
-                        },
-                        || if let Trace::Err(e) = (group.systems[1].execute)(ecs.clone()) {
-                            panic!("System Scheduler for stage {} encountered error in system {} with trace: \n {}"
-                            , self.stage, group.systems[1].name, e);
-                        }
+                        || a[0].run(ecs.clone(), run),
+                        || b[0].run(ecs.clone(), run),
                     );
                 },
                 _ => {
                     // use par_iter for any length larger than 2.
-                    group.systems.par_iter().for_each(|node| {
-                        if let Trace::Err(e) = (node.execute)(ecs.clone()) {
-                            panic!("System Scheduler for stage {} encountered error in system {} with trace: \n {}"
-                            , self.stage, node.name, e);
-                        }
+                    group.systems.par_iter_mut().for_each(|node| {
+                        node.run(ecs.clone(), run);
                     });
                 }
             }
         }
     }
+
+    /// Starts every system on the rayon pool as soon as the systems it depends on are done.
+    fn execute_dynamic(&mut self, ecs: Ptr<Ecs>, run: Run) {
+        let dependents = &self.dependents;
+
+        let main: Vec<bool> = self.groups.iter()
+            .flat_map(|group| group.systems.iter())
+            .map(|node| node.main_thread)
+            .collect();
+
+        // every node is only ever run by one task, the locks are never contended.
+        let nodes: Vec<Mutex<&mut Node>> = self.groups.iter_mut()
+            .flat_map(|group| group.systems.iter_mut())
+            .map(Mutex::new)
+            .collect();
+
+        let remaining: Vec<AtomicUsize> = self.dependencies.iter()
+            .map(|count| AtomicUsize::new(*count))
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        let dispatch = Dispatch { nodes: &nodes, remaining: &remaining, dependents, main: &main, sender, ecs, run };
+
+        // the body of an in place scope runs on this thread, so systems that
+        // have to run here are sent back to it once they are ready.
+        rayon::in_place_scope(|scope| {
+            for (i, count) in self.dependencies.iter().enumerate() {
+                if *count == 0 {
+                    dispatch.start(scope, i);
+                }
+            }
+
+            for _ in 0..main.iter().filter(|main| **main).count() {
+                let Some(i) = receive(&receiver) else {
+                    // a system panicked, so the ones after it will never be ready.
+                    break
+                };
+
+                dispatch.run(i);
+                dispatch.finish(scope, i);
+            }
+        });
+    }
+
+    /// Works out which systems each system has to wait for when run by the dynamic
+    /// executor: the ones in earlier groups that it conflicts with or is ordered after.
+    /// This keeps every conflicting pair in the same order as the grouped executor.
+    fn plan_dependencies(&mut self) {
+        let nodes: Vec<(usize, &Node)> = self.groups.iter()
+            .enumerate()
+            .flat_map(|(g, group)| group.systems.iter().map(move |node| (g, node)))
+            .collect();
+
+        self.dependencies = vec![0; nodes.len()];
+        self.dependents = vec![Vec::new(); nodes.len()];
+
+        for (j, (group_b, b)) in nodes.iter().enumerate() {
+            for (i, (group_a, a)) in nodes[..j].iter().enumerate() {
+                let ordered = a.order.before.contains(&b.name) || b.order.after.contains(&a.name);
+
+                if group_a < group_b && (ordered || conflicts(a, b)) {
+                    self.dependents[i].push(j);
+                    self.dependencies[j] += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Everything a task of the dynamic executor needs to run a system and start the next ones.
+struct Dispatch<'a, 'n> {
+    nodes: &'a [Mutex<&'n mut Node>],
+    remaining: &'a [AtomicUsize],
+    dependents: &'a [Vec<usize>],
+    main: &'a [bool],
+    sender: Sender<usize>,
+    ecs: Ptr<Ecs>,
+    run: Run,
+}
+
+/// Sent instead of a system when one panics, to stop waiting for the rest.
+const ABORT: usize = usize::MAX;
+
+/// The next system that has to run on this thread, or `None` if a system panicked.
+fn receive(receiver: &Receiver<usize>) -> Option<usize> {
+    // outside of the pool nothing else needs this thread, so it can block.
+    if rayon::current_thread_index().is_none() {
+        return receiver.recv().ok().filter(|i| *i != ABORT);
+    }
+
+    // inside of it, blocking could starve the systems this one is waiting on.
+    loop {
+        match receiver.try_recv() {
+            Ok(i) => return Some(i).filter(|i| *i != ABORT),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {
+                if rayon::yield_now() != Some(rayon::Yield::Executed) {
+                    thread::yield_now();
+                }
+            },
+        }
+    }
+}
+
+impl<'a, 'n> Dispatch<'a, 'n> {
+    /// Starts the system at `i`, on the pool or on the thread that runs the schedule.
+    fn start<'s>(&'s self, scope: &rayon::Scope<'s>, i: usize) {
+        if self.main[i] {
+            let _ = self.sender.send(i);
+            return
+        }
+
+        scope.spawn(move |scope| {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.run(i))) {
+                let _ = self.sender.send(ABORT);
+                panic::resume_unwind(payload);
+            }
+
+            self.finish(scope, i);
+        });
+    }
+
+    fn run(&self, i: usize) {
+        self.nodes[i].lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .run(self.ecs.clone(), self.run);
+    }
+
+    /// Starts every system that was only waiting on the one at `i`.
+    fn finish<'s>(&'s self, scope: &rayon::Scope<'s>, i: usize) {
+        for dependent in self.dependents[i].iter() {
+            // the last dependency to finish starts the dependent.
+            if self.remaining[*dependent].fetch_sub(1, Ordering::AcqRel) == 1 {
+                self.start(scope, *dependent);
+            }
+        }
+    }
+}
+
+/// How the systems of a stage are run.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Executor {
+    /// Runs groups of systems that don't conflict in parallel, waiting 
+    /// for a whole group to finish before starting the next one.
+    #[default]
+    Grouped,
+    /// Starts every system on the thread pool as soon as the systems it conflicts 
+    /// with or is ordered after have finished, without waiting for whole groups.
+    MultiThreaded,
+    /// Runs every system on the calling thread, one at a time, in the same 
+    /// order every frame. Useful for debugging and deterministic runs.
+    SingleThreaded,
+}
+
+/// A system that returned an error or panicked while its stage was running.
+#[derive(Debug)]
+pub struct SystemError {
+    pub stage: &'static str,
+    pub system: &'static str,
+    pub error: EcsError,
+}
+
+impl Display for SystemError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "system {} in stage {} failed: {}", self.system, self.stage, self.error)
+    }
+}
+
+/// Called for every system that returns an error, or panics 
+/// while panics are being caught. 
+pub type ErrorHandler = fn(SystemError);
+
+/// Prints the error and lets the rest of the systems keep running.
+pub fn log_error(error: SystemError) {
+    eprintln!("{}", error);
+}
+
+/// What the scheduler does when a system panics.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnPanic {
+    /// Let the panic unwind out of the scheduler, which usually ends the process.
+    #[default]
+    Unwind,
+    /// Catch the panic, report it as [`EcsError::Panicked`] and keep running the system on later frames.
+    Report,
+    /// Catch the panic, report it as [`EcsError::Panicked`] and stop running the system.
+    Disable,
+}
+
+/// What `finalize` does with systems that conflict, but have no order between them,
+/// so which one runs first depends on how the stage happened to be grouped.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AmbiguityCheck {
+    /// Only list them in [`StageInfo::ambiguities`].
+    #[default]
+    Ignore,
+    /// Print every one of them as it is found.
+    Warn,
+    /// Fail with [`EcsError::Ambiguous`].
+    Deny,
+}
+
+/// How a scheduler deals with systems that fail.
+#[derive(Copy, Clone)]
+pub struct Recovery {
+    pub on_error: ErrorHandler,
+    pub on_panic: OnPanic,
+}
+
+impl Default for Recovery {
+    fn default() -> Self {
+        Self {
+            on_error: log_error,
+            on_panic: OnPanic::Unwind,
+        }
+    }
 }
 
 struct Group {
@@ -132,46 +666,168 @@ struct Group {
 }
 
 struct Node {
-    execute: fn(Ptr<Ecs>) -> Trace<()>,
-    access: fn(&mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Trace<()> ,
+    execute: SystemFn,
+    locals: Locals,
+    access: fn(&mut Vec<Accessor>, ecs: Ptr<Ecs>) -> Result<()> ,
     accessors: Vec<Accessor>,
     edges: Vec<usize>,
     name: &'static str,
+    order: Order,
+    conditions: Vec<RunCondition>,
+    exclusive: bool,
+    /// Whether the system has to run on the thread that runs the schedule.
+    main_thread: bool,
+    enabled: bool,
+    sample: Option<Sample>,
+}
+
+/// When and where a system ran the last time it was profiled.
+#[derive(Copy, Clone)]
+struct Sample {
+    start: Instant,
+    duration: Duration,
+    thread: ThreadId,
+}
+
+/// One profiled run of a system, handed to [`Diagnostics`](super::diagnostics::Diagnostics).
+pub(crate) struct SystemSample {
+    pub group: usize,
+    pub name: &'static str,
+    pub start: Instant,
+    pub duration: Duration,
+    pub thread: ThreadId,
+    pub entities: usize,
+    pub commands: usize,
 }
 
-#[derive(PartialEq)]
+/// Settings for running the systems of a stage once.
+#[derive(Copy, Clone)]
+struct Run {
+    stage: &'static str,
+    recovery: Recovery,
+    profile: bool,
+}
+
+impl Node {
+    fn run(&mut self, ecs: Ptr<Ecs>, run: Run) {
+        let Run { stage, recovery, profile } = run;
+
+        self.sample = None;
+        if !self.enabled {
+            return
+        }
+
+        // skipped systems keep their last run, so they still see what changed since.
+        for condition in self.conditions.iter_mut() {
+            condition.locals.rewind_condition(self.locals.this_run);
+
+            match (condition.evaluate)(ecs.clone(), &mut condition.locals) {
+                Ok(true) => {},
+                Ok(false) => return,
+                Err(error) => {
+                    (recovery.on_error)(SystemError { stage, system: self.name, error });
+                    return
+                }
+            }
+        }
+
+        self.locals.rewind(profile, ecs.next_tick());
+        let start = profile.then(Instant::now);
+
+        let result = match recovery.on_panic {
+            OnPanic::Unwind => (self.execute)(ecs, &mut self.locals),
+            OnPanic::Report | OnPanic::Disable => {
+                // The world may be left half updated by the system, but 
+                // every component is still in a valid state to be dropped. 
+                let execute = AssertUnwindSafe(|| (self.execute)(ecs, &mut self.locals));
+
+                match panic::catch_unwind(execute) {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        self.enabled = recovery.on_panic != OnPanic::Disable;
+                        Err(EcsError::Panicked(panic_message(payload)))
+                    }
+                }
+            }
+        };
+
+        if let Some(start) = start {
+            self.sample = Some(Sample {
+                start,
+                duration: start.elapsed(),
+                thread: thread::current().id(),
+            });
+        }
+
+        if let Err(error) = result {
+            (recovery.on_error)(SystemError { stage, system: self.name, error });
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Accessor {
     ResMut(u16),
     ResRef(u16),
     Ref(u16),
     Mut(u16),
+    /// The system has to run on the thread that runs its schedule. Conflicts with nothing.
+    MainThread,
 }
 
-fn conflicts(nodea: &Node, nodeb: &Node) -> bool {
-    for accessor in nodea.accessors.iter() {
-        match *accessor {
-            Accessor::Ref(id) => {
-                if nodeb.accessors.contains(&Accessor::Mut(id)) {
-                    return true
-                }
-            },
-            Accessor::Mut(id) => {
-                if nodeb.accessors.contains(&Accessor::Mut(id)) || nodeb.accessors.contains(&Accessor::Ref(id)) {
-                    return true
-                }
-            },
-            Accessor::ResRef(id) => {
-                if nodeb.accessors.contains(&Accessor::ResMut(id)) {
-                    return true
-                }
-            },
-            Accessor::ResMut(id) => {
-                if nodeb.accessors.contains(&Accessor::ResMut(id)) || nodeb.accessors.contains(&Accessor::ResRef(id)) {
-                    return true
-                }
+/// Whether `a` is ordered to run before `b`, directly or through other systems.
+fn runs_before(before: &[Vec<usize>], a: usize, b: usize) -> bool {
+    let mut visited = vec![false; before.len()];
+    let mut stack = vec![b];
+
+    while let Some(node) = stack.pop() {
+        for pred in before[node].iter() {
+            if *pred == a {
+                return true
+            }
+
+            if !visited[*pred] {
+                visited[*pred] = true;
+                stack.push(*pred);
             }
         }
     }
 
     false
-}
\ No newline at end of file
+}
+
+fn conflicts(nodea: &Node, nodeb: &Node) -> bool {
+    nodea.exclusive || nodeb.exclusive || !conflicts_between(&nodea.accessors, &nodeb.accessors).is_empty()
+}
+
+/// Every pair of accessors from `a` and `b` that can't be used at the same time,
+/// which is why two systems with them can't run in parallel. 
+pub fn conflicts_between(a: &[Accessor], b: &[Accessor]) -> Vec<(Accessor, Accessor)> {
+    let mut conflicts = Vec::new();
+
+    for accessor in a.iter() {
+        let conflicting: &[Accessor] = match *accessor {
+            Accessor::Ref(id) => &[Accessor::Mut(id)],
+            Accessor::Mut(id) => &[Accessor::Mut(id), Accessor::Ref(id)],
+            Accessor::ResRef(id) => &[Accessor::ResMut(id)],
+            Accessor::ResMut(id) => &[Accessor::ResMut(id), Accessor::ResRef(id)],
+            Accessor::MainThread => &[],
+        };
+
+        for other in b.iter().filter(|other| conflicting.contains(other)) {
+            conflicts.push((*accessor, *other));
+        }
+    }
+
+    conflicts
+}
@@ -0,0 +1,41 @@
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::anon::Anon;
+use super::error::{EcsError, Result};
+use super::handle::Component;
+use super::{start_trace, trace};
+
+/// Writes a component to, and reads it back from, RON text.
+#[derive(Copy, Clone)]
+pub struct ComponentSerializer {
+    serialize: fn(&Anon) -> Result<String>,
+    deserialize: fn(ron::Value) -> ron::Result<Anon>,
+}
+
+impl ComponentSerializer {
+    pub fn of<C: Component + Serialize + DeserializeOwned>() -> Self {
+        Self {
+            serialize: serialize::<C>,
+            deserialize: |value| Ok(Anon::new(value.into_rust::<C>()?)),
+        }
+    }
+
+    /// Writes `anon` as RON, failing unless it holds the component this serializer is for.
+    pub fn serialize(&self, anon: &Anon) -> Result<String> {
+        (self.serialize)(anon)
+    }
+
+    /// Reads the component this serializer is for from `value`.
+    pub fn deserialize(&self, value: ron::Value) -> ron::Result<Anon> {
+        (self.deserialize)(value)
+    }
+}
+
+fn serialize<C: Component + Serialize>(anon: &Anon) -> Result<String> {
+    match ron::to_string(trace!(anon.downcast::<C>())) {
+        Ok(text) => Ok(text),
+        Err(err) => start_trace!(EcsError::SerializeFailed(C::name(), err.to_string())),
+    }
+}
@@ -1,97 +1,367 @@
+use std::any::{TypeId, type_name};
+use std::collections::HashMap;
+use std::time::Instant;
 
-use std::any::type_name;
-
-use super::error::Trace;
-use super::params::Fetch;
-use super::scheduler::Scheduler;
+use super::scheduler::{Scheduler, Accessor, AmbiguityCheck, ErrorHandler, Executor, OnPanic, Recovery};
+use super::introspect::{ScheduleInfo, ScheduleStages};
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
 use super::handle::Handle;
-use super::params::System;
+use super::params::{IntoSystem, ResMut};
+use super::diagnostics::Diagnostics;
 use super::ptr::Ptr;
 use super::ecs::Ecs;
 
+/// The schedule that `execute_startup` runs once.
+pub struct Startup;
+
+/// The schedule that `execute_systems` runs every frame.
+pub struct Update;
+
 pub struct Systems {
-    startup: Vec<Scheduler>,   
-    systems: Vec<Scheduler>,
+    schedules: Vec<Schedule>,
+    /// The index of every schedule, by the type of its label.
+    labels: HashMap<TypeId, usize>,
+    /// The schedule and position within it of every stage, by the type of the stage.
+    stages: HashMap<TypeId, (usize, usize)>,
+    recovery: Recovery,
+    ambiguity: AmbiguityCheck,
+    executor: Executor,
+}
+
+/// A list of stages that run one after another, identified by a label type.
+pub struct Schedule {
+    name: &'static str,
+    stages: Vec<Scheduler>,
+    /// Whether the stages have been taken out to run.
+    running: bool,
 }
 
 impl Systems {
     pub fn new() -> Self {
-        Self {
-            startup: Vec::new(),
-            systems: Vec::new(),
+        let mut systems = Self {
+            schedules: Vec::new(),
+            labels: HashMap::new(),
+            stages: HashMap::new(),
+            recovery: Recovery::default(),
+            ambiguity: AmbiguityCheck::default(),
+            executor: Executor::default(),
+        };
+
+        // always the first two.
+        systems.labels.insert(TypeId::of::<Startup>(), 0);
+        systems.schedules.push(Schedule::new(type_name::<Startup>()));
+        systems.labels.insert(TypeId::of::<Update>(), 1);
+        systems.schedules.push(Schedule::new(type_name::<Update>()));
+
+        systems
+    }
+
+    /// Sets the function that is called for every system that returns an error.
+    /// Defaults to [`log_error`].
+    pub fn set_error_handler(&mut self, on_error: ErrorHandler) {
+        self.recovery.on_error = on_error;
+    }
+
+    /// Sets what happens when a system panics. Defaults to [`OnPanic::Unwind`].
+    pub fn set_panic_policy(&mut self, on_panic: OnPanic) {
+        self.recovery.on_panic = on_panic;
+    }
+
+    /// Sets how the systems of every stage are run. Defaults to [`Executor::Grouped`].
+    pub fn set_executor(&mut self, executor: Executor) {
+        self.executor = executor;
+    }
+
+    /// Sets what `execute_startup` does with conflicting systems that have no order
+    /// between them. Defaults to [`AmbiguityCheck::Ignore`].
+    pub fn set_ambiguity_check(&mut self, check: AmbiguityCheck) {
+        self.ambiguity = check;
+    }
+
+    /// Adds an empty schedule labelled `L`.
+    pub fn add_schedule<L: Handle>(&mut self) -> Result<()> {
+        if self.schedule::<L>().is_some() {
+            start_trace!(EcsError::DuplicateSchedule(type_name::<L>()));
+        }
+
+        self.labels.insert(TypeId::of::<L>(), self.schedules.len());
+        self.schedules.push(Schedule::new(type_name::<L>()));
+
+        Ok(())
+    }
+
+    /// Adds the stage `H` to the end of the schedule labelled `L`.
+    pub fn add_stage<L: Handle, H: Handle>(&mut self) -> Result<()> {
+        if self.stages.contains_key(&TypeId::of::<H>()) {
+            start_trace!(EcsError::DuplicateStage(type_name::<H>()));
+        }
+
+        let Some(index) = self.schedule::<L>() else {
+            start_trace!(EcsError::ScheduleNotFound(type_name::<L>()))
+        };
+
+        let schedule = &mut self.schedules[index];
+        if schedule.running {
+            start_trace!(EcsError::ScheduleRunning(schedule.name));
+        }
+
+        self.stages.insert(TypeId::of::<H>(), (index, schedule.stages.len()));
+        schedule.stages.push(Scheduler::new(type_name::<H>()));
+
+        Ok(())
+    }
+
+    /// Removes the stage `H` and every system in it. The stage can be added again later.
+    pub fn remove_stage<H: Handle>(&mut self) -> Result<()> {
+        let Some((index, stage)) = self.stages.get(&TypeId::of::<H>()).copied() else {
+            start_trace!(EcsError::StageNotFound(type_name::<H>()))
+        };
+
+        let schedule = &mut self.schedules[index];
+        if schedule.running {
+            start_trace!(EcsError::ScheduleRunning(schedule.name));
         }
+
+        schedule.stages.remove(stage);
+        self.stages.remove(&TypeId::of::<H>());
+
+        // the stages after it in the same schedule move down by one.
+        for (s, i) in self.stages.values_mut() {
+            if *s == index && *i > stage {
+                *i -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_startup_stage<H: Handle>(&mut self) -> Result<()> {
+        self.add_stage::<Startup, H>()
+    }
+
+    pub fn add_systems_stage<H: Handle>(&mut self) -> Result<()> {
+        self.add_stage::<Update, H>()
+    }
+
+    /// Adds `system` to the stage `H`, in whichever schedule the stage belongs to.
+    /// The stage is planned again before the next time it runs. Fails if a system
+    /// with the same name is already in the stage, as systems are told apart by name.
+    pub fn add_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        let stage = trace!(self.stage_mut::<H>());
+        stage.add_system(system.into_system())
     }
 
-    pub fn add_startup_stage<H: Handle>(&mut self) {
+    /// Removes `system` from the stage `H`. The stage is planned again before the next time it runs.
+    pub fn remove_system<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        let name = system.into_system().name;
+        let stage = trace!(self.stage_mut::<H>());
 
-        if *H::handle() == u16::MAX {
-            panic!("Tried to add the same startup stage handle twice! Name: {}", type_name::<H>());
+        if !stage.remove_system(name) {
+            start_trace!(EcsError::SystemNotFound { stage: type_name::<H>(), system: name });
         }
 
-        *H::handle() = self.startup.len() as u16;
-        self.startup.push(Scheduler::new(type_name::<H>()));
+        Ok(())
     }
 
-    pub fn add_systems_stage<H: Handle>(&mut self) {
+    /// Sets whether `system` in the stage `H` runs. Disabled systems keep their place,
+    /// so toggling a system never plans the stage again.
+    pub fn set_system_enabled<H: Handle, M>(&mut self, system: impl IntoSystem<M>, enabled: bool) -> Result<()> {
+        let name = system.into_system().name;
+        let stage = trace!(self.stage_mut::<H>());
 
-        if *H::handle() == u16::MAX {
-            panic!("Tried to add the same system stage handle twice! Name: {}", type_name::<H>());
+        if !stage.set_enabled(name, enabled) {
+            start_trace!(EcsError::SystemNotFound { stage: type_name::<H>(), system: name });
         }
 
-        *H::handle() = self.systems.len() as u16;
-        self.systems.push(Scheduler::new(type_name::<H>()));
+        Ok(())
     }
 
-    pub fn add_startup<S: System + Fetch, H: Handle>(&mut self) {
-        if *H::handle() == u16::MAX {
-            panic!("Tried to add startup system {} to stage {}, which has not been declared!"
-            , type_name::<S>(), type_name::<H>());
+    /// Whether `system` in the stage `H` runs. Systems are disabled by 
+    /// [`set_system_enabled`](Self::set_system_enabled), or by [`OnPanic::Disable`].
+    pub fn is_system_enabled<H: Handle, M>(&self, system: impl IntoSystem<M>) -> Result<bool> {
+        let name = system.into_system().name;
+
+        let Some((schedule, stage)) = self.stages.get(&TypeId::of::<H>()).copied() else {
+            start_trace!(EcsError::StageNotFound(type_name::<H>()))
+        };
+
+        match self.schedules[schedule].stages.get(stage).and_then(|stage| stage.is_enabled(name)) {
+            Some(enabled) => Ok(enabled),
+            None => start_trace!(EcsError::SystemNotFound { stage: type_name::<H>(), system: name }),
         }
+    }
 
-        if *H::handle() >= self.startup.len() as u16 {
-            panic!("Tried to add startup system {} to stage {}, which is invalid! 
-            (handle contained a value greater than the stage limit)", type_name::<S>(), type_name::<H>());
+    /// The stage `H`, as long as its schedule isn't running.
+    fn stage_mut<H: Handle>(&mut self) -> Result<&mut Scheduler> {
+        let Some((schedule, stage)) = self.stages.get(&TypeId::of::<H>()).copied() else {
+            start_trace!(EcsError::StageNotFound(type_name::<H>()))
+        };
+
+        let schedule = &mut self.schedules[schedule];
+        if schedule.running {
+            start_trace!(EcsError::ScheduleRunning(schedule.name));
         }
 
-        self.startup[*H::handle() as usize].add_system::<S>();
+        Ok(&mut schedule.stages[stage])
+    }
+
+    pub fn add_startup<H: Handle, M>(&mut self, system: impl IntoSystem<M>) -> Result<()> {
+        self.add_system::<H, M>(system)
     }
 
-    pub fn add_system<S: System + Fetch, H: Handle>(&mut self) {
-        if *H::handle() == u16::MAX {
-            panic!("Tried to add system {} to stage {}, which has not been declared!"
-            , type_name::<S>(), type_name::<H>());
+    /// Finalizes every schedule, so that conflicts and ordering cycles are found
+    /// before anything runs, then runs the [`Startup`] schedule.
+    pub fn execute_startup(ecs: Ptr<Ecs>) -> Result<()> {
+        // no system is running yet, so nothing else is using the systems.
+        let systems = unsafe { &mut (*ecs.get_mut()).systems };
+
+        for schedule in systems.schedules.iter_mut() {
+            trace!(schedule.finalize(ecs.clone(), systems.ambiguity));
         }
 
-        if *H::handle() >= self.startup.len() as u16 {
-            panic!("Tried to add system {} to stage {}, which is invalid! 
-            (handle contained a value greater than the stage limit)", type_name::<S>(), type_name::<H>());
+        trace!(Self::run_schedule(ecs.clone(), 0, type_name::<Startup>()));
+        Self::end_frame(&ecs);
+
+        Ok(())
+    }
+
+    pub fn execute_systems(ecs: Ptr<Ecs>) -> Result<()> {
+        trace!(Self::run_schedule(ecs.clone(), 1, type_name::<Update>()));
+        Self::end_frame(&ecs);
+
+        Ok(())
+    }
+
+    /// Runs the schedule at `index`, finalizing the stages that changed since it last ran.
+    ///
+    /// The stages are taken out of the schedule while they run, so that exclusive
+    /// systems can add to and run other schedules without aliasing the running one.
+    pub(crate) fn run_schedule(ecs: Ptr<Ecs>, index: usize, name: &'static str) -> Result<()> {
+        let (stages, recovery, executor) = {
+            let systems = unsafe { &mut (*ecs.get_mut()).systems };
+
+            let Some(schedule) = systems.schedules.get_mut(index).filter(|schedule| schedule.name == name) else {
+                start_trace!(EcsError::ScheduleNotFound(name))
+            };
+
+            if schedule.running {
+                start_trace!(EcsError::ScheduleRunning(name));
+            }
+
+            trace!(schedule.finalize(ecs.clone(), systems.ambiguity));
+            schedule.running = true;
+
+            (std::mem::take(&mut schedule.stages), systems.recovery, systems.executor)
+        };
+
+        // puts the stages back even if a system panics through the loop.
+        let mut running = Running { ecs: ecs.clone(), index, stages };
+
+        for stage in running.stages.iter_mut() {
+            Self::run_stage(stage, ecs.clone(), recovery, executor);
+
+            // every system of the stage is done, so nothing else is using the world.
+            trace!(unsafe { (*ecs.get_mut()).flush() });
         }
 
-        self.systems[*H::handle() as usize].add_system::<S>();
+        Ok(())
     }
 
-    pub fn execute_startup(&mut self, ecs: Ptr<Ecs>) {
-        for startup in self.startup.iter_mut() {
-            startup.finalize(ecs.clone());
+    /// Executes `stage`, profiling it while there are [`Diagnostics`] to record it into.
+    fn run_stage(stage: &mut Scheduler, ecs: Ptr<Ecs>, recovery: Recovery, executor: Executor) {
+        if !ecs.contains_resource::<Diagnostics>() {
+            stage.execute(ecs, recovery, executor, false);
+            return
         }
 
-        for startup in self.startup.iter() {
-            startup.execute(ecs.clone());
+        let start = Instant::now();
+        stage.execute(ecs.clone(), recovery, executor, true);
+
+        if let Some(mut diagnostics) = Self::diagnostics(&ecs) {
+            diagnostics.record(stage.name(), start, start.elapsed(), stage.samples());
         }
+    }
 
-        for system in self.systems.iter_mut() {
-            system.finalize(ecs.clone());
+    /// Closes the trace events recorded since the last frame. A frame is one run of
+    /// [`Startup`] or [`Update`], along with any schedule that runs in between.
+    fn end_frame(ecs: &Ecs) {
+        if let Some(mut diagnostics) = Self::diagnostics(ecs) {
+            diagnostics.end_frame();
         }
     }
 
-    pub fn execute_systems(&self, ecs: Ptr<Ecs>) {
-        for system in self.systems.iter() {
-            system.execute(ecs.clone());
+    /// The diagnostics, looked up for every use rather than held on to, as the systems
+    /// in between can remove them or run other schedules that record into them.
+    fn diagnostics(ecs: &Ecs) -> Option<ResMut<'_, Diagnostics>> {
+        ecs.resources.get_resource_mut::<Diagnostics>(0, ecs.next_tick()).ok()
+    }
+
+    /// The index of the schedule labelled `L`, if it has been added.
+    pub(crate) fn schedule<L: Handle>(&self) -> Option<usize> {
+        self.labels.get(&TypeId::of::<L>()).copied()
+    }
+
+    pub fn info(&self, name_of: &dyn Fn(Accessor) -> &'static str) -> ScheduleInfo {
+        ScheduleInfo {
+            schedules: self.schedules.iter().map(|schedule| ScheduleStages {
+                name: schedule.name,
+                stages: schedule.stages.iter().map(|stage| stage.info(name_of)).collect(),
+                running: schedule.running,
+            }).collect(),
         }
     }
 }
 
+/// The stages of a running schedule, which are put back into it when the run ends,
+/// however it ends.
+struct Running {
+    ecs: Ptr<Ecs>,
+    index: usize,
+    stages: Vec<Scheduler>,
+}
 
+impl Drop for Running {
+    fn drop(&mut self) {
+        // the run is over, so nothing else is using the systems.
+        let systems = unsafe { &mut (*self.ecs.get_mut()).systems };
+        let schedule = &mut systems.schedules[self.index];
+        schedule.stages = std::mem::take(&mut self.stages);
+        schedule.running = false;
+    }
+}
 
+impl Schedule {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            stages: Vec::new(),
+            running: false,
+        }
+    }
 
+    /// Plans every stage again that had systems added or removed since the last time.
+    fn finalize(&mut self, ecs: Ptr<Ecs>, check: AmbiguityCheck) -> Result<()> {
+        if self.running {
+            return Ok(());
+        }
+
+        for stage in self.stages.iter_mut().filter(|stage| !stage.is_finalized()) {
+            trace!(stage.finalize(ecs.clone(), check));
+        }
+
+        Ok(())
+    }
+}
 
+impl Default for Systems {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the schedule labelled `L`. As an exclusive system, this runs
+/// one schedule from a stage of another.
+pub fn run_schedule<L: Handle>(ecs: &mut Ecs) -> Result<()> {
+    ecs.run_schedule::<L>()
+}
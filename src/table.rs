@@ -4,11 +4,16 @@ use indexmap::IndexMap;
 use super::handle::Component;
 use super::archetypes::ComponentId;
 use super::anon::AnonVec;
+use super::anon::ComponentInfo;
+use super::bundle::{Bundle, DynamicBundle};
+use super::components::RequiredComponent;
 use super::package::Package;
 use super::archetypes::Column;
 use super::anon::AnonIter;
 use super::package::PackageIndexIter;
 use super::commands::Modify;
+use super::error::{EcsError, Result};
+use super::{start_trace, trace};
 
 pub struct Table {
     rows: IndexMap<ComponentId, AnonVec>,
@@ -16,86 +21,200 @@ pub struct Table {
 }
 
 impl Table {
-    pub fn new(mut packages: Vec<Package>) -> Self {
-        let len = packages.len();
-        let mut rows = IndexMap::new();
+    /// Creates an empty table with a row for each component in `infos`. 
+    pub fn new(infos: &[ComponentInfo]) -> Self {
+        let mut rows = IndexMap::with_capacity(infos.len());
 
-        if let Some(mut package) = packages.pop() {
-            while let Some(anon) = package.pop() {
-                rows.insert(anon.id(), AnonVec::new(anon));
-            }
-        } else {
-            panic!("Attempted to spawn an empty package vector!")
+        for info in infos.iter() {
+            rows.insert(info.id, AnonVec::empty(*info));
         }
 
-        while let Some(mut package) = packages.pop() {
-            while let Some(anon) = package.pop() {
-                if let Some(row) = rows.get_mut(&anon.id()) {
-                    row.push(anon);
-                } else {
-                    panic!("Invalid Package! ID Not Found!")
-                }
+        Self {
+            rows, len: 0,
+        }
+    }
+
+    /// Moves the components of `bundle` into a new column at the end of the table,
+    /// along with the default value of every component in `required`. `infos` holds
+    /// the resolved info of every component of the bundle followed by the required
+    /// ones, and together they must be exactly the components of this table.
+    pub(crate) fn push<B: DynamicBundle>(&mut self, bundle: B, infos: &[ComponentInfo], required: &[RequiredComponent]) -> Result<()> {
+        // Check the bundle before taking anything out of it, 
+        // so a mismatch can't leave the rows with different lengths.
+        trace!(self.matches(infos));
+
+        let rows = self.rows_of(infos);
+        let (rows, required_rows) = rows.split_at(rows.len() - required.len());
+        let mut next = rows.iter();
+
+        bundle.take_dyn(&mut |ptr| {
+            let row = *next.next().unwrap();
+            unsafe { self.rows[row].push_raw(ptr) };
+        });
+
+        for (req, row) in required.iter().zip(required_rows.iter()) {
+            trace!(self.rows[*row].push((req.default)()));
+        }
+
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// The position of the row of every component in `infos`, which must all be in the table.
+    fn rows_of(&self, infos: &[ComponentInfo]) -> Vec<usize> {
+        infos.iter()
+            .map(|info| self.rows.get_index_of(&info.id).unwrap())
+            .collect()
+    }
+
+    /// Errors unless `infos` holds exactly the components of this table.
+    fn matches(&self, infos: &[ComponentInfo]) -> Result<()> {
+        for info in infos.iter() {
+            if !self.rows.contains_key(&info.id) {
+                start_trace!(EcsError::ComponentMismatch(info.name))
             }
         }
 
-        Self {
-            rows, len, 
+        if infos.len() != self.rows.len() {
+            start_trace!(EcsError::Internal("spawned a bundle that did not match its table"))
         }
+
+        Ok(())
     }
 
-    pub fn spawn(&mut self, mut packages: Vec<Package>) {
-        self.len += packages.len();
+    /// Moves every bundle from `iter` into new columns at the end of the table,
+    /// each one along with the default value of every component in `required`,
+    /// with `infos` as in [`push`](Self::push). Space is reserved once up front, 
+    /// and the row each component goes into is only looked up once for the whole batch. 
+    pub(crate) fn push_batch<B, I>(&mut self, iter: I, infos: &[ComponentInfo], required: &[RequiredComponent]) -> Result<()>
+    where
+        B: Bundle,
+        I: IntoIterator<Item = B>,
+    {
+        trace!(self.matches(infos));
 
-        while let Some(mut package) = packages.pop() {
-            while let Some(anon) = package.pop() {
-                if let Some(row) = self.rows.get_mut(&anon.id()) {
-                    row.push(anon);
-                } else {
-                    panic!("Attempted to spawn invalid component!")
-                }
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+
+        // the row of every component, in the order the bundle hands them out,
+        // followed by the rows of the required components.
+        let rows = self.rows_of(infos);
+        let (rows, required_rows) = rows.split_at(rows.len() - required.len());
+
+        for bundle in iter {
+            let mut next = rows.iter();
+
+            bundle.take(&mut |ptr| {
+                let row = *next.next().unwrap();
+                unsafe { self.rows[row].push_raw(ptr) };
+            });
+
+            for (req, row) in required.iter().zip(required_rows.iter()) {
+                trace!(self.rows[*row].push((req.default)()));
             }
+
+            self.len += 1;
         }
+
+        Ok(())
     }
 
-    pub fn destroy(&mut self, mut destroys: Vec<(Column, bool)>) {
-        self.len -= destroys.len();
+    /// Moves every column of `other` onto the end of this table, leaving `other` empty.
+    /// Both tables must store the same components. 
+    pub fn append(&mut self, other: &mut Table) -> Result<()> {
+        let infos: Vec<ComponentInfo> = other.rows.values().map(|row| *row.info()).collect();
+        trace!(self.matches(&infos));
+
+        for (id, row) in other.rows.iter_mut() {
+            trace!(self.rows[id].append(row));
+        }
+
+        self.len += other.len;
+        other.len = 0;
+
+        Ok(())
+    }
+
+    /// Calls the `on_add` hooks of every component from column `start` to the end of the table.
+    pub(crate) fn on_add(&mut self, start: Column) {
+        for (_, row) in self.rows.iter_mut() {
+            row.on_add(start);
+        }
+    }
+
+    /// Makes sure there is space for at least `additional` more columns.
+    pub fn reserve(&mut self, additional: usize) {
+        for (_, row) in self.rows.iter_mut() {
+            row.reserve(additional);
+        }
+    }
+
+    pub fn destroy(&mut self, mut destroys: Vec<(Column, bool)>) -> Result<()> {
+        destroys.par_sort_unstable_by_key(|(col, _)| *col);
 
-        destroys.par_sort_by(|a, b| {
-            a.0.partial_cmp(&b.0).unwrap()
+        // A column can only be destroyed once. If any request 
+        // for it wants a drop, the components are dropped.
+        destroys.dedup_by(|a, b| {
+            if a.0 == b.0 {
+                b.1 |= a.1;
+                true
+            } else {
+                false
+            }
         });
 
-        destroys.dedup();
+        if let Some((col, _)) = destroys.last() {
+            if *col >= self.len {
+                start_trace!(EcsError::IndexOutOfBounds { index: *col, len: self.len })
+            }
+        }
+
+        self.len -= destroys.len();
 
+        // Columns are destroyed from back to front, so the element swapped
+        // into a destroyed column is never one that is waiting to be destroyed.
         while let Some((col, drop)) = destroys.pop() {
             if drop == NO_DROP {
                 for (_, row) in self.rows.iter_mut() {
-                    row.destroy_nodrop(col);
+                    trace!(row.destroy_nodrop(col));
                 }
             } else {
                 for (_, row) in self.rows.iter_mut() {
-                    row.destroy_swap(col);
+                    trace!(row.destroy_swap(col));
                 }
             }
         }
+
+        Ok(())
     }
 
-    pub fn contains(&self, ids: &Vec<ComponentId>) -> bool {
+    pub fn contains(&self, ids: &[ComponentId]) -> bool {
         for id in ids.iter() {
             if !self.rows.contains_key(id) { return false }
         }
         true
     }
 
-    pub fn collect<C: Component>(&self) -> Option<AnonIter<C>> {
-        if self.len == 0 { return None }
+    /// Iterates over the row of `C`, which has the id `id` in this Ecs.
+    ///
+    /// # Safety
+    /// The same as `AnonVec::iter_unchecked`.
+    pub(crate) unsafe fn collect<C: Component>(&self, id: ComponentId) -> Result<Option<AnonIter<'_, C>>> {
+        if self.len == 0 { return Ok(None) }
 
-        if let Some(row) = self.rows.get(C::handle()) {
-            return Some(row.iter_as::<C>())
+        if let Some(row) = self.rows.get(&id) {
+            Ok(Some(trace!(row.iter_unchecked::<C>())))
         } else {
-            panic!("Attempted to collect component from archetype in which it does not exist")
+            start_trace!(EcsError::ComponentMismatch(C::name()))
         }
     }
 
+    /// The row that stores component `id`, if this table has one.
+    pub(crate) fn row(&self, id: ComponentId) -> Option<&AnonVec> {
+        self.rows.get(&id)
+    }
+
     pub fn collect_indices(&self, table: usize) -> Option<PackageIndexIter> {
         if self.len == 0 {
             None
@@ -108,26 +227,96 @@ impl Table {
         }
     }
 
-    pub fn extract(&self, mut modify: Modify, col: Column) -> Package {
+    /// Moves the components at `col` into a [`Package`] and applies `modify` to it.
+    /// The column must be destroyed with `NO_DROP` afterwards, as the package
+    /// now owns its components. 
+    pub(crate) fn extract(&self, mut modify: Modify, col: Column) -> Result<Package> {
         let mut package = Package::new();
         for (_, row) in self.rows.iter() {
-            package.insert_anon(row.index(col));
+            package.insert_anon(trace!(unsafe { row.read(col) }));
         }
 
         while let Some(destroy) = modify.remove.pop() {
             package.remove(destroy);
         }
 
-        while let Some(insert) = modify.insert.pop() {
+        for insert in modify.insert.drain(..) {
+            insert.on_add();
             package.insert_anon(insert);
         }
 
-        package
+        // required components are only added if the entity doesn't already have them.
+        for req in modify.require.iter() {
+            if !package.contains(&req.info) {
+                let anon = (req.default)();
+                anon.on_add();
+                package.insert_anon(anon);
+            }
+        }
+
+        Ok(package)
+    }
+
+    /// The components an entity of this table has once `modify` is applied to it, 
+    /// so that where it moves can be checked before anything is moved out of it.
+    pub(crate) fn infos_after(&self, modify: &Modify) -> Vec<ComponentInfo> {
+        let mut infos: Vec<ComponentInfo> = self.rows.values()
+            .map(|row| *row.info())
+            .filter(|info| !modify.remove.contains(&info.id))
+            .collect();
+
+        let added = modify.insert.iter()
+            .map(|anon| anon.info())
+            .chain(modify.require.iter().map(|req| &req.info));
+
+        for info in added {
+            if !infos.iter().any(|other| other.is(info)) {
+                infos.push(*info);
+            }
+        }
+
+        infos
     }
-}
 
-unsafe impl Send for Table {}
-unsafe impl Sync for Table {}
+    /// Clones the components at `col` into a [`Package`]. Fails before cloning
+    /// anything if any of the components can't be cloned.
+    pub fn clone_column(&self, col: Column) -> Result<Package> {
+        trace!(self.cloneable());
+
+        let mut package = Package::new();
+        for (_, row) in self.rows.iter() {
+            package.insert_anon(trace!(row.clone_at(col)));
+        }
+
+        Ok(package)
+    }
+
+    /// Errors unless every component of the table can be cloned.
+    pub fn cloneable(&self) -> Result<()> {
+        for (_, row) in self.rows.iter() {
+            if row.info().clone.is_none() {
+                start_trace!(EcsError::NotCloneable(row.info().name))
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Releases any memory held by the table that is not storing components.
+    pub fn shrink_to_fit(&mut self) {
+        for (_, row) in self.rows.iter_mut() {
+            row.shrink_to_fit();
+        }
+    }
+}
 
 pub const NO_DROP: bool = false;
 pub const DO_DROP: bool = true;
\ No newline at end of file
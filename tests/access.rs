@@ -0,0 +1,47 @@
+use rylans_ecs::*;
+
+#[derive(Component)]
+struct Position;
+
+#[derive(Resource, Default)]
+struct Score;
+
+fn aliasing_queries(_: Query<(Mut<Position>,)>, _: Query<(Ref<Position>,)>) {}
+
+fn aliasing_resources(_: ResMut<Score>, _: ResMut<Score>) {}
+
+fn shared_reads(_: Query<(Ref<Position>,)>, _: Query<(Ref<Position>,)>, _: ResRef<Score>, _: ResRef<Score>) {}
+
+#[test]
+fn aliasing_parameters_are_rejected() {
+    struct Queries;
+    struct Resources;
+    struct Reads;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.init_resource::<Score>();
+
+    ecs.add_system_stage::<Queries>().unwrap();
+    ecs.add_system::<Queries, _>(aliasing_queries).unwrap();
+
+    let error = ecs.execute_startup().unwrap_err();
+    let EcsError::AccessConflict { system, a, b } = error.kind() else {
+        panic!("expected an access conflict, got {}", error)
+    };
+
+    assert!(system.ends_with("aliasing_queries"));
+    assert!(a.contains("Position") && b.contains("Position"));
+
+    ecs.remove_stage::<Queries>().unwrap();
+    ecs.add_system_stage::<Resources>().unwrap();
+    ecs.add_system::<Resources, _>(aliasing_resources).unwrap();
+    assert!(matches!(ecs.execute_startup().unwrap_err().kind(), EcsError::AccessConflict { .. }));
+
+    // reading the same thing twice is fine.
+    ecs.remove_stage::<Resources>().unwrap();
+    ecs.add_system_stage::<Reads>().unwrap();
+    ecs.add_system::<Reads, _>(shared_reads).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+}
@@ -0,0 +1,189 @@
+use rylans_ecs::*;
+
+#[derive(Component, Clone, Debug, PartialEq)]
+#[component(clone)]
+struct Position(u64);
+
+#[derive(Component, Clone, Debug, PartialEq)]
+#[component(clone)]
+struct Velocity(u64);
+
+#[derive(Resource, Default)]
+struct Spawned(Vec<Reserved>);
+
+fn position(ecs: &Ecs, entity: PackageIndex) -> u64 {
+    let id = ecs.components().id_of::<Position>().unwrap();
+    let ptr = ecs.get_raw(entity, id).unwrap().unwrap();
+
+    unsafe { (*ptr.cast::<Position>()).0 }
+}
+
+fn spawner(mut commands: Commands, mut spawned: ResMut<Spawned>) {
+    spawned.0 = commands.spawn_batch((0..3).map(|i| (Position(i), Velocity(i)))).unwrap();
+    spawned.0.push(commands.spawn((Position(10),)).unwrap());
+    spawned.0.push(commands.spawn((Velocity(0), Position(11))).unwrap());
+}
+
+#[test]
+fn ecs_spawn_batch() {
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.add_component::<Velocity>().unwrap();
+
+    let entities = ecs.spawn_batch((0..100).map(|i| (Position(i), Velocity(i)))).unwrap();
+    assert_eq!(entities.len(), 100);
+
+    for (i, entity) in entities.iter().enumerate() {
+        assert_eq!(position(&ecs, *entity), i as u64);
+    }
+
+    let more = ecs.spawn_batch((100..105).map(|i| (Velocity(i), Position(i)))).unwrap();
+    assert_eq!(more[0], PackageIndex { table: entities[0].table, col: 100 });
+}
+
+#[test]
+fn commands_spawn_batch() {
+    struct Spawn;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.add_component::<Velocity>().unwrap();
+    ecs.add_resource(Spawned::default());
+
+    // an entity that is already there, so not every entity lands in a new table.
+    ecs.spawn((Position(100),)).unwrap();
+
+    ecs.add_system_stage::<Spawn>().unwrap();
+    ecs.add_system::<Spawn, _>(spawner).unwrap();
+    ecs.execute_startup().unwrap();
+
+    for _ in 0..2 {
+        ecs.execute_systems().unwrap();
+
+        let spawned = ecs.get_resource_ref::<Spawned>().unwrap().0.clone();
+        let positions: Vec<u64> = spawned.iter().map(|entity| position(&ecs, ecs.resolve(*entity).unwrap())).collect();
+        assert_eq!(positions, vec![0, 1, 2, 10, 11]);
+    }
+}
+
+/// The entities to clone.
+#[derive(Resource, Default)]
+struct Originals(Vec<PackageIndex>);
+
+fn cloner(query: Query<(Ref<Position>,)>, mut commands: Commands, originals: ResRef<Originals>) {
+    for (_, entity) in query.iter().unwrap() {
+        if originals.0.contains(&entity) {
+            commands.clone_entity(entity).unwrap();
+        }
+    }
+
+    commands.submit().unwrap();
+}
+
+#[test]
+fn commands_clone_entity() {
+    struct Cloning;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.add_component::<Velocity>().unwrap();
+
+    let original = ecs.spawn((Position(1), Velocity(2))).unwrap();
+    ecs.add_resource(Originals(vec![original]));
+
+    ecs.add_system_stage::<Cloning>().unwrap();
+    ecs.add_system::<Cloning, _>(cloner).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+
+    let copy = PackageIndex { table: original.table, col: original.col + 1 };
+    assert_eq!(position(&ecs, copy), 1);
+
+    let velocity = ecs.components().id_of::<Velocity>().unwrap();
+    let ptr = ecs.get_raw(copy, velocity).unwrap().unwrap();
+    assert_eq!(unsafe { &*ptr.cast::<Velocity>() }, &Velocity(2));
+}
+
+#[derive(Resource)]
+struct Template(Prefab);
+
+#[derive(Resource, Default)]
+struct Instances(Vec<PrefabInstance<Reserved>>);
+
+fn instantiate(mut commands: Commands, template: ResRef<Template>, mut instances: ResMut<Instances>) {
+    let instance = commands.spawn_prefab(&template.0, (Position(5),)).unwrap();
+    instances.0.push(instance);
+}
+
+#[test]
+fn commands_spawn_prefab() {
+    struct Instantiate;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.add_component::<Velocity>().unwrap();
+
+    let prefab = Prefab::new()
+        .with(Position(0))
+        .with(Velocity(1))
+        .with_child(Prefab::new().with(Position(2)));
+
+    ecs.add_resource(Template(prefab));
+    ecs.add_resource(Instances::default());
+
+    ecs.add_system_stage::<Instantiate>().unwrap();
+    ecs.add_system::<Instantiate, _>(instantiate).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+
+    let instances = ecs.get_resource_ref::<Instances>().unwrap().0.clone();
+    assert_eq!(instances.len(), 1);
+
+    for instance in instances.iter() {
+        let instance = instance.try_map(&mut |reserved| ecs.resolve(reserved)).unwrap();
+
+        assert_eq!(position(&ecs, instance.root), 5);
+        assert_eq!(instance.children.len(), 1);
+        assert_eq!(position(&ecs, instance.children[0].root), 2);
+    }
+}
+
+/// The entity to destroy, and the one spawned in its place.
+#[derive(Resource)]
+struct Replace {
+    destroy: PackageIndex,
+    spawned: Option<Reserved>,
+}
+
+fn replace(mut commands: Commands, mut replace: ResMut<Replace>) {
+    commands.destroy(replace.destroy).unwrap();
+    replace.spawned = Some(commands.spawn((Position(42),)).unwrap());
+    commands.submit().unwrap();
+}
+
+#[test]
+fn spawn_and_destroy_in_one_stage() {
+    struct Replacing;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+
+    let entities: Vec<PackageIndex> = (0..3).map(|i| ecs.spawn((Position(i),)).unwrap()).collect();
+    ecs.add_resource(Replace { destroy: entities[0], spawned: None });
+
+    ecs.add_system_stage::<Replacing>().unwrap();
+    ecs.add_system::<Replacing, _>(replace).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+
+    // the destroyed entity's column is filled before the new entity is appended.
+    let spawned = ecs.get_resource_ref::<Replace>().unwrap().spawned.unwrap();
+    let index = ecs.resolve(spawned).unwrap();
+    assert_eq!(index, PackageIndex { table: entities[0].table, col: 2 });
+    assert_eq!(position(&ecs, index), 42);
+
+    // the reservation is stale once anything else is flushed.
+    ecs.get_resource_mut::<Replace>().unwrap().destroy = index;
+    ecs.execute_systems().unwrap();
+    assert!(matches!(ecs.resolve(spawned).unwrap_err().kind(), EcsError::UnresolvedSpawn));
+}
@@ -0,0 +1,19 @@
+use std::alloc::Layout;
+
+use rylans_ecs::*;
+
+#[test]
+fn component_ids_run_out() {
+    let mut ecs = Ecs::new();
+
+    let info = |name: String| unsafe {
+        ComponentInfo::dynamic(Box::leak(name.into_boxed_str()), Layout::new::<u32>(), None, None)
+    };
+
+    for i in 0..u16::MAX {
+        assert_eq!(ecs.add_dynamic_component(info(i.to_string())).unwrap(), i);
+    }
+
+    let err = ecs.add_dynamic_component(info("last".to_string())).unwrap_err();
+    assert!(matches!(err.kind(), EcsError::TooManyComponents("last")));
+}
@@ -0,0 +1,35 @@
+use rylans_ecs::*;
+
+struct Outer;
+struct Inner;
+struct InnerStage;
+
+fn nested(ecs: &mut Ecs) -> Result<()> {
+    ecs.run_schedule::<Inner>()
+}
+
+fn work() {}
+
+#[test]
+fn one_frame_per_update() {
+    let mut ecs = Ecs::new();
+    ecs.enable_diagnostics(8);
+
+    ecs.add_system_stage::<Outer>().unwrap();
+    ecs.add_schedule::<Inner>().unwrap();
+    ecs.add_stage::<Inner, InnerStage>().unwrap();
+
+    ecs.add_system::<Outer, _>(nested).unwrap();
+    ecs.add_system::<InnerStage, _>(work).unwrap();
+    ecs.execute_startup().unwrap();
+
+    for _ in 0..3 {
+        ecs.execute_systems().unwrap();
+    }
+
+    let diagnostics = ecs.get_resource_ref::<Diagnostics>().unwrap();
+    assert_eq!(diagnostics.frames(), 3);
+
+    let stage = diagnostics.stage(std::any::type_name::<InnerStage>()).unwrap();
+    assert_eq!(stage.timings.history().count(), 3);
+}
@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rylans_ecs::*;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts how many times a value of it was dropped.
+#[derive(Component)]
+struct Counted;
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Component)]
+struct Marker;
+
+/// What `step` does to `entity` in the next update.
+#[derive(Resource)]
+enum Step {
+    Destroy(PackageIndex),
+    Move(PackageIndex),
+    Replace(PackageIndex),
+    Remove(PackageIndex),
+    Nothing,
+}
+
+fn step(mut commands: Commands, mut step: ResMut<Step>) {
+    match *step {
+        Step::Destroy(entity) => commands.destroy(entity).unwrap(),
+        // moves the entity to another table, which must not drop its components.
+        Step::Move(entity) => commands.modify(entity, |modify| { modify.with(Marker); }).unwrap(),
+        Step::Replace(entity) => commands.modify(entity, |modify| { modify.with(Counted); }).unwrap(),
+        Step::Remove(entity) => commands.modify(entity, |modify| { modify.without::<Counted>(); }).unwrap(),
+        Step::Nothing => {},
+    }
+
+    commands.submit().unwrap();
+    *step = Step::Nothing;
+}
+
+#[test]
+fn components_drop_exactly_once() {
+    struct Steps;
+
+    let drops = || DROPS.load(Ordering::SeqCst);
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Counted>().unwrap();
+    ecs.add_component::<Marker>().unwrap();
+
+    let entities: Vec<PackageIndex> = (0..3).map(|_| ecs.spawn((Counted,)).unwrap()).collect();
+    assert_eq!(drops(), 0);
+
+    ecs.add_resource(Step::Nothing);
+    ecs.add_system_stage::<Steps>().unwrap();
+    ecs.add_system::<Steps, _>(step).unwrap();
+    ecs.execute_startup().unwrap();
+
+    // the last entity is destroyed, so the others keep their places.
+    let mut run = |next: Step| {
+        *ecs.get_resource_mut::<Step>().unwrap() = next;
+        ecs.execute_systems().unwrap();
+        drops()
+    };
+
+    assert_eq!(run(Step::Destroy(entities[2])), 1);
+    assert_eq!(run(Step::Move(entities[1])), 1);
+    assert_eq!(run(Step::Replace(entities[0])), 2);
+    assert_eq!(run(Step::Remove(entities[0])), 3);
+
+    // the entity that moved, with its Counted, and the one that lost it are left.
+    drop(ecs);
+    assert_eq!(drops(), 4);
+}
@@ -0,0 +1,89 @@
+use rylans_ecs::*;
+
+#[derive(Component, Debug, PartialEq)]
+struct Position(u64);
+
+#[derive(Component, Debug, PartialEq)]
+struct Velocity(u64);
+
+#[derive(Component)]
+struct Frozen;
+
+#[test]
+fn read_fetches_and_with_only_matches() {
+    let mut ecs = Ecs::new();
+    let position = ecs.add_component::<Position>().unwrap();
+    let velocity = ecs.add_component::<Velocity>().unwrap();
+
+    ecs.spawn((Position(1), Velocity(2))).unwrap();
+    ecs.spawn((Position(3),)).unwrap();
+
+    let query = QueryBuilder::new().read(position).with(velocity).build(&ecs).unwrap();
+    let rows: Vec<DynamicRow> = query.iter(&ecs).unwrap().collect();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].ptrs().len(), 1);
+    assert_eq!(unsafe { &*rows[0].get(0).unwrap().cast::<Position>() }, &Position(1));
+}
+
+/// The label of the query `movement` fetches.
+struct Moving;
+
+fn movement(mut query: Dynamic<Moving>) {
+    for row in query.iter_mut().unwrap() {
+        let position = unsafe { &mut *row.get(0).unwrap().cast::<Position>() };
+        let velocity = unsafe { &*row.get(1).unwrap().cast::<Velocity>() };
+
+        position.0 += velocity.0;
+    }
+}
+
+#[test]
+fn dynamic_queries_in_systems() {
+    struct Move;
+
+    let mut ecs = Ecs::new();
+    let position = ecs.add_component::<Position>().unwrap();
+    let velocity = ecs.add_component::<Velocity>().unwrap();
+    let frozen = ecs.add_component::<Frozen>().unwrap();
+
+    let moving = ecs.spawn((Position(0), Velocity(2))).unwrap();
+    let stuck = ecs.spawn((Position(0), Velocity(2), Frozen)).unwrap();
+
+    let builder = QueryBuilder::new().write(position).read(velocity).without(frozen);
+    ecs.add_dynamic_query::<Moving>(builder).unwrap();
+    assert!(ecs.add_dynamic_query::<Moving>(QueryBuilder::new()).is_err());
+
+    ecs.add_system_stage::<Move>().unwrap();
+    ecs.add_system::<Move, _>(movement).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+    ecs.execute_systems().unwrap();
+
+    let read = |entity| unsafe { (*ecs.get_raw(entity, position).unwrap().unwrap().cast::<Position>()).0 };
+    assert_eq!(read(moving), 4);
+    assert_eq!(read(stuck), 0);
+}
+
+#[test]
+fn dynamic_query_access_conflicts() {
+    struct Stage;
+
+    fn reader(_: Query<(Ref<Position>,)>) {}
+
+    let mut ecs = Ecs::new();
+    let position = ecs.add_component::<Position>().unwrap();
+    ecs.add_dynamic_query::<Moving>(QueryBuilder::new().write(position)).unwrap();
+
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(movement).unwrap();
+    ecs.add_system::<Stage, _>(reader).unwrap();
+    ecs.execute_startup().unwrap();
+
+    let info = ecs.schedule_info();
+    let stage = info.schedules.iter().flat_map(|schedule| schedule.stages.iter())
+        .find(|stage| stage.name.ends_with("Stage"))
+        .unwrap();
+
+    assert_eq!(stage.conflicts.len(), 1);
+}
@@ -0,0 +1,79 @@
+use rylans_ecs::*;
+
+#[derive(Component)]
+struct Value(u64);
+
+#[derive(Component)]
+struct Step(u64);
+
+#[derive(Resource, Default)]
+struct Log(Vec<&'static str>);
+
+#[derive(Resource, Default)]
+struct Total(u64);
+
+fn double(mut query: Query<(Mut<Value>,)>, mut log: ResMut<Log>) {
+    for (value, _) in query.iter_mut().unwrap() {
+        value.0 *= 2;
+    }
+
+    log.0.push("double");
+}
+
+fn advance(mut query: Query<(Mut<Value>, Ref<Step>)>, mut log: ResMut<Log>) {
+    for (value, step, _) in query.iter_mut().unwrap() {
+        value.0 += step.0;
+    }
+
+    log.0.push("advance");
+}
+
+fn total(query: Query<(Ref<Value>,)>, mut total: ResMut<Total>) {
+    total.0 = query.iter().unwrap().map(|(value, _)| value.0).sum();
+}
+
+/// Only touches its own local, so it can run alongside anything.
+fn busy(mut count: Local<u64>) {
+    *count += 1;
+}
+
+fn run(executor: Executor) -> (Vec<&'static str>, u64) {
+    struct Update;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Value>().unwrap();
+    ecs.add_component::<Step>().unwrap();
+    ecs.init_resource::<Log>();
+    ecs.init_resource::<Total>();
+    ecs.set_executor(executor);
+
+    for i in 0..50 {
+        ecs.spawn((Value(i), Step(i % 3))).unwrap();
+    }
+
+    ecs.add_system_stage::<Update>().unwrap();
+    ecs.add_system::<Update, _>(total.after(advance)).unwrap();
+    ecs.add_system::<Update, _>(advance.after(double)).unwrap();
+    ecs.add_system::<Update, _>(double).unwrap();
+    ecs.add_system::<Update, _>(busy).unwrap();
+    ecs.execute_startup().unwrap();
+
+    for _ in 0..10 {
+        ecs.execute_systems().unwrap();
+    }
+
+    let log = ecs.get_resource_ref::<Log>().unwrap().0.clone();
+    let total = ecs.get_resource_ref::<Total>().unwrap().0;
+
+    (log, total)
+}
+
+#[test]
+fn executors_agree() {
+    let (log, total) = run(Executor::SingleThreaded);
+    assert_eq!(log.len(), 20);
+
+    for executor in [Executor::Grouped, Executor::MultiThreaded] {
+        assert_eq!(run(executor), (log.clone(), total));
+    }
+}
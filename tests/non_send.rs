@@ -0,0 +1,73 @@
+use std::rc::Rc;
+use std::thread::{self, ThreadId};
+
+use rylans_ecs::*;
+
+/// The Ecs isn't `Send`, but systems on worker threads see it all the same.
+struct Shared(*mut Ecs);
+
+unsafe impl Send for Shared {}
+
+impl Shared {
+    fn ecs<'a>(self) -> &'a mut Ecs {
+        unsafe { &mut *self.0 }
+    }
+}
+
+#[test]
+fn only_the_owner_moves_non_send_resources() {
+    let mut ecs = Ecs::new();
+    assert!(ecs.insert_non_send(Rc::new(1)).unwrap().is_none());
+
+    let shared = Shared(&mut ecs);
+    std::thread::spawn(move || {
+        let ecs = shared.ecs();
+        assert!(ecs.get_non_send_ref::<Rc<i32>>().is_err());
+        assert!(matches!(ecs.remove_non_send::<Rc<i32>>().unwrap_err().kind(), EcsError::WrongThread(_)));
+    }).join().unwrap();
+
+    let shared = Shared(&mut ecs);
+    std::thread::spawn(move || {
+        // the value is never moved out of this thread, as the insert fails.
+        let replaced = shared.ecs().insert_non_send(Rc::new(2));
+        assert!(matches!(replaced.map(|_| ()).unwrap_err().kind(), EcsError::WrongThread(_)));
+    }).join().unwrap();
+
+    assert_eq!(*ecs.insert_non_send(Rc::new(3)).unwrap().unwrap(), 1);
+    assert_eq!(*ecs.remove_non_send::<Rc<i32>>().unwrap().unwrap(), 3);
+}
+
+/// Records the thread it runs on, which has to be the one running the schedule.
+fn on_main_thread(mut threads: NonSendMut<Vec<ThreadId>>, _: NonSend<Rc<()>>) {
+    threads.push(thread::current().id());
+}
+
+/// Keeps the thread pool busy alongside `on_main_thread`.
+fn busy(mut count: Local<u64>) {
+    *count += 1;
+}
+
+#[test]
+fn non_send_systems_run_on_the_main_thread() {
+    struct Update;
+
+    for executor in [Executor::SingleThreaded, Executor::Grouped, Executor::MultiThreaded] {
+        let mut ecs = Ecs::new();
+        ecs.insert_non_send(Vec::<ThreadId>::new()).unwrap();
+        ecs.insert_non_send(Rc::new(())).unwrap();
+        ecs.set_executor(executor);
+
+        ecs.add_system_stage::<Update>().unwrap();
+        ecs.add_system::<Update, _>(busy).unwrap();
+        ecs.add_system::<Update, _>(on_main_thread).unwrap();
+        ecs.execute_startup().unwrap();
+
+        for _ in 0..10 {
+            ecs.execute_systems().unwrap();
+        }
+
+        let threads = ecs.get_non_send_ref::<Vec<ThreadId>>().unwrap();
+        assert_eq!(threads.len(), 10);
+        assert!(threads.iter().all(|id| *id == thread::current().id()));
+    }
+}
@@ -0,0 +1,160 @@
+#![cfg(feature = "serialize")]
+
+use rylans_ecs::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[component(clone, serialize)]
+struct Health(u32);
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[component(clone, serialize)]
+struct Weapon {
+    damage: u32,
+}
+
+mod game {
+    use rylans_ecs::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[component(clone, serialize)]
+    pub struct Position {
+        pub x: f32,
+        pub y: f32,
+    }
+}
+
+mod other {
+    use rylans_ecs::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[component(clone, serialize)]
+    pub struct Position(pub u32);
+}
+
+#[derive(Component, Clone)]
+#[component(clone)]
+struct Hidden;
+
+#[derive(Component, Serialize, Deserialize)]
+#[component(serialize)]
+struct Unique(u32);
+
+const EXAMPLE: &str = r#"
+(
+    components: {
+        "Health": (100),
+        "game::Position": (x: 0.0, y: 0.0),
+    },
+    children: [
+        (components: { "Weapon": (damage: 5) }),
+    ],
+)
+"#;
+
+fn world() -> Ecs {
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Health>().unwrap();
+    ecs.add_component::<Weapon>().unwrap();
+    ecs.add_component::<game::Position>().unwrap();
+    ecs.add_component::<other::Position>().unwrap();
+    ecs.add_component::<Hidden>().unwrap();
+    ecs.add_component::<Unique>().unwrap();
+    ecs
+}
+
+fn get<C: Component + Clone>(ecs: &Ecs, entity: PackageIndex) -> Option<C> {
+    let id = ecs.components().id_of::<C>().unwrap();
+    let ptr = ecs.get_raw(entity, id).unwrap()?;
+
+    Some(unsafe { (*ptr.cast::<C>()).clone() })
+}
+
+#[test]
+fn spawn_the_documented_example() {
+    let mut ecs = world();
+    let prefab = Prefab::from_ron(EXAMPLE, ecs.components()).unwrap();
+
+    assert_eq!(prefab.components().len(), 2);
+    assert_eq!(prefab.children().len(), 1);
+
+    let instance = ecs.spawn_prefab(&prefab).unwrap();
+    assert_eq!(get::<Health>(&ecs, instance.root), Some(Health(100)));
+    assert_eq!(get::<game::Position>(&ecs, instance.root), Some(game::Position { x: 0.0, y: 0.0 }));
+    assert_eq!(get::<Weapon>(&ecs, instance.root), None);
+
+    assert_eq!(instance.children.len(), 1);
+    let child = instance.children[0].root;
+    assert_eq!(get::<Weapon>(&ecs, child), Some(Weapon { damage: 5 }));
+    assert_eq!(get::<Health>(&ecs, child), None);
+}
+
+#[test]
+fn spawn_with_overrides() {
+    let mut ecs = world();
+    let prefab = Prefab::from_ron(EXAMPLE, ecs.components()).unwrap();
+
+    let instance = ecs.spawn_prefab_with(&prefab, (Health(5),)).unwrap();
+    assert_eq!(get::<Health>(&ecs, instance.root), Some(Health(5)));
+    assert_eq!(get::<game::Position>(&ecs, instance.root), Some(game::Position { x: 0.0, y: 0.0 }));
+
+    // the prefab itself is left as it was.
+    let instance = ecs.spawn_prefab(&prefab).unwrap();
+    assert_eq!(get::<Health>(&ecs, instance.root), Some(Health(100)));
+}
+
+#[test]
+fn names_are_looked_up() {
+    let ecs = world();
+
+    let full = format!("(components: {{ {:?}: (7) }})", std::any::type_name::<Health>());
+    let prefab = Prefab::from_ron(&full, ecs.components()).unwrap();
+    assert_eq!(prefab.components()[0].downcast::<Health>().unwrap(), &Health(7));
+
+    let prefab = Prefab::from_ron(r#"(components: { "other::Position": (3) })"#, ecs.components()).unwrap();
+    assert_eq!(prefab.components()[0].downcast::<other::Position>().unwrap(), &other::Position(3));
+
+    // two components end in `::Position`, so the short name is ambiguous.
+    let err = Prefab::from_ron(r#"(components: { "Position": (3) })"#, ecs.components()).err().unwrap();
+    assert!(matches!(err.kind(), EcsError::UnknownComponentName(_)));
+
+    let err = Prefab::from_ron(r#"(components: { "Missing": () })"#, ecs.components()).err().unwrap();
+    assert!(matches!(err.kind(), EcsError::UnknownComponentName(_)));
+}
+
+#[test]
+fn components_have_to_be_serializable_and_cloneable() {
+    let ecs = world();
+
+    let err = Prefab::from_ron(r#"(components: { "Hidden": () })"#, ecs.components()).err().unwrap();
+    assert!(matches!(err.kind(), EcsError::NotSerializable(_)));
+
+    let err = Prefab::from_ron(r#"(components: { "Unique": (1) })"#, ecs.components()).err().unwrap();
+    assert!(matches!(err.kind(), EcsError::NotCloneable(_)));
+
+    let err = Prefab::from_ron(r#"(components: { "Health": (x: 1) })"#, ecs.components()).err().unwrap();
+    assert!(matches!(err.kind(), EcsError::InvalidPrefab(_)));
+}
+
+#[test]
+fn round_trip_through_values() {
+    let ecs = world();
+
+    let weapon = Anon::new(Weapon { damage: 12 });
+    let health = Anon::new(Health(40));
+
+    for (anon, id) in [(&weapon, ecs.components().id_of::<Weapon>()), (&health, ecs.components().id_of::<Health>())] {
+        let serializer = ecs.components().info(id.unwrap()).unwrap().serializer().unwrap();
+
+        let text = serializer.serialize(anon).unwrap();
+        let value = ron::from_str::<ron::Value>(&text).unwrap();
+        let back = serializer.deserialize(value).unwrap();
+
+        assert_eq!(serializer.serialize(&back).unwrap(), text);
+    }
+
+    let serializer = ecs.components().info(ecs.components().id_of::<Weapon>().unwrap()).unwrap().serializer().unwrap();
+    assert!(matches!(serializer.serialize(&health).unwrap_err().kind(), EcsError::ComponentMismatch(_)));
+}
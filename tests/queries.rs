@@ -0,0 +1,48 @@
+use rylans_ecs::*;
+
+#[derive(Component, Debug, PartialEq)]
+struct Position(u64);
+
+#[derive(Component, Debug, PartialEq)]
+struct Velocity(u64);
+
+#[derive(Resource, Default)]
+struct Sum(u64);
+
+fn movement(mut query: Query<(Mut<Position>, Ref<Velocity>)>) {
+    for (position, velocity, _) in query.iter_mut().unwrap() {
+        position.0 += velocity.0;
+    }
+}
+
+fn sum(query: Query<(Ref<Position>,)>, mut sum: ResMut<Sum>) {
+    // read-only queries can be iterated more than once at a time.
+    let pairs = query.iter().unwrap().zip(query.iter().unwrap());
+    sum.0 = pairs.map(|((a, _), (b, _))| a.0 + b.0).sum();
+}
+
+#[test]
+fn iter_and_iter_mut() {
+    struct Move;
+    struct Sums;
+
+    let mut ecs = Ecs::new();
+    ecs.add_component::<Position>().unwrap();
+    ecs.add_component::<Velocity>().unwrap();
+    ecs.init_resource::<Sum>();
+
+    ecs.spawn((Position(0), Velocity(1))).unwrap();
+    ecs.spawn((Position(10), Velocity(2))).unwrap();
+
+    ecs.add_system_stage::<Move>().unwrap();
+    ecs.add_system_stage::<Sums>().unwrap();
+    ecs.add_system::<Move, _>(movement).unwrap();
+    ecs.add_system::<Sums, _>(sum).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+
+    assert_eq!(ecs.get_resource_ref::<Sum>().unwrap().0, 2 * (1 + 12));
+
+    ecs.get_resource_mut::<Sum>().unwrap().0 = 0;
+    assert_eq!(ecs.get_resource_ref::<Sum>().unwrap().0, 0);
+}
@@ -0,0 +1,67 @@
+use rylans_ecs::*;
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+struct Vec2 {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Component, Reflect)]
+#[component(reflect)]
+struct Body {
+    position: Vec2,
+    mass: f64,
+}
+
+/// Claims to have the fields of a `Body`, which is much larger.
+struct Liar(#[allow(dead_code)] u8);
+
+impl Reflect for Liar {
+    fn type_info() -> TypeInfo {
+        Body::type_info()
+    }
+}
+
+impl Component for Liar {
+    fn reflect() -> Option<fn() -> TypeInfo> {
+        Some(<Liar as Reflect>::type_info)
+    }
+}
+
+#[derive(Component, Reflect)]
+#[component(reflect)]
+struct Holder(Liar);
+
+#[test]
+fn fields_by_path() {
+    let mut ecs = Ecs::new();
+    let body = ecs.add_component::<Body>().unwrap();
+
+    let entity = ecs.spawn((Body { position: Vec2 { x: 1.0, y: 2.0 }, mass: 3.0 },)).unwrap();
+
+    assert_eq!(ecs.components().type_info(body).unwrap().paths(), vec!["position.x", "position.y", "mass"]);
+    assert_eq!(*ecs.field::<f32>(entity, body, "position.y").unwrap(), 2.0);
+
+    *ecs.field_mut::<Vec2>(entity, body, "position").unwrap() = Vec2 { x: 5.0, y: 6.0 };
+    assert_eq!(*ecs.field::<f32>(entity, body, "position.x").unwrap(), 5.0);
+
+    let err = ecs.field::<u32>(entity, body, "mass").unwrap_err();
+    assert!(matches!(err.kind(), EcsError::FieldMismatch { .. }));
+}
+
+#[test]
+fn reflecting_another_type() {
+    let mut ecs = Ecs::new();
+
+    let err = ecs.add_component::<Liar>().unwrap_err();
+    assert!(matches!(err.kind(), EcsError::ReflectMismatch { .. }));
+
+    let anon = Anon::new(Liar(0));
+    assert!(matches!(anon.field::<f64>("mass").unwrap_err().kind(), EcsError::ReflectMismatch { .. }));
+
+    // a field whose type reflects another type can't be looked into.
+    let holder = ecs.add_component::<Holder>().unwrap();
+    let entity = ecs.spawn((Holder(Liar(0)),)).unwrap();
+    assert!(ecs.field::<Liar>(entity, holder, "0").is_ok());
+    assert!(matches!(ecs.field::<f64>(entity, holder, "0.mass").unwrap_err().kind(), EcsError::FieldNotFound { .. }));
+}
@@ -0,0 +1,97 @@
+use rylans_ecs::*;
+
+#[derive(Resource, Default)]
+struct Settings(u64);
+
+#[derive(Resource, Default)]
+struct Reloads(u64);
+
+#[derive(Resource, Default)]
+struct Seen {
+    added: Vec<bool>,
+    changed: Vec<bool>,
+}
+
+fn reload(mut reloads: ResMut<Reloads>) {
+    reloads.0 += 1;
+}
+
+fn observe(settings: ResRef<Settings>, mut seen: ResMut<Seen>) {
+    seen.added.push(settings.is_added());
+    seen.changed.push(settings.is_changed());
+}
+
+#[test]
+fn resource_changed_across_frames() {
+    struct Reload;
+
+    let mut ecs = Ecs::new();
+    ecs.init_resource::<Settings>();
+    ecs.init_resource::<Reloads>();
+    ecs.init_resource::<Seen>();
+
+    ecs.add_system_stage::<Reload>().unwrap();
+    ecs.add_system::<Reload, _>(reload.run_if(resource_changed::<Settings>)).unwrap();
+    ecs.add_system::<Reload, _>(observe).unwrap();
+    ecs.execute_startup().unwrap();
+
+    // the first run sees the resource as added and changed.
+    ecs.execute_systems().unwrap();
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Reloads>().unwrap().0, 1);
+
+    ecs.get_resource_mut::<Settings>().unwrap().0 = 5;
+    ecs.execute_systems().unwrap();
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Reloads>().unwrap().0, 2);
+
+    // reading through a mutable borrow without writing is not a change.
+    let _ = ecs.get_resource_mut::<Settings>().unwrap().0;
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Reloads>().unwrap().0, 2);
+
+    let seen = ecs.get_resource_ref::<Seen>().unwrap();
+    assert_eq!(seen.added, vec![true, false, false, false, false]);
+    assert_eq!(seen.changed, vec![true, false, true, false, false]);
+}
+
+fn configure(mut settings: ResMut<Settings>) {
+    settings.0 += 1;
+}
+
+#[test]
+fn conditions_declare_their_access() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.init_resource::<Settings>();
+    ecs.init_resource::<Reloads>();
+
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(reload.run_if(resource_changed::<Settings>)).unwrap();
+    ecs.add_system::<Stage, _>(configure).unwrap();
+    ecs.execute_startup().unwrap();
+
+    let info = ecs.schedule_info();
+    let stage = info.schedules.iter().flat_map(|schedule| schedule.stages.iter())
+        .find(|stage| stage.name.ends_with("Stage"))
+        .unwrap();
+
+    // the condition reads what `configure` writes, so the two can't share a group.
+    assert_eq!(stage.conflicts.len(), 1);
+}
+
+#[test]
+fn conditions_may_read_what_their_system_writes() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.init_resource::<Settings>();
+
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(configure.run_if(resource_exists::<Settings>)).unwrap();
+    ecs.execute_startup().unwrap();
+    ecs.execute_systems().unwrap();
+
+    assert_eq!(ecs.get_resource_ref::<Settings>().unwrap().0, 1);
+}
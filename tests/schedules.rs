@@ -0,0 +1,166 @@
+use rylans_ecs::*;
+
+struct First;
+struct Second;
+struct Extra;
+
+#[derive(Resource, Default)]
+struct Runs(u32);
+
+fn count(mut runs: ResMut<Runs>) {
+    runs.0 += 1;
+}
+
+fn world() -> Ecs {
+    let mut ecs = Ecs::new();
+    ecs.add_resource(Runs::default());
+
+    ecs.add_system_stage::<First>().unwrap();
+    ecs.add_system_stage::<Second>().unwrap();
+    ecs.add_schedule::<Extra>().unwrap();
+    ecs.add_stage::<Extra, Extra>().unwrap();
+
+    ecs.add_system::<Second, _>(count).unwrap();
+    ecs.add_system::<Extra, _>(count).unwrap();
+    ecs.execute_startup().unwrap();
+
+    ecs
+}
+
+#[test]
+fn labels_are_per_ecs() {
+    let mut a = world();
+    let mut b = world();
+
+    a.execute_systems().unwrap();
+    b.run_schedule::<Extra>().unwrap();
+    b.execute_systems().unwrap();
+
+    assert_eq!(a.get_resource_ref::<Runs>().unwrap().0, 1);
+    assert_eq!(b.get_resource_ref::<Runs>().unwrap().0, 2);
+
+    // removing a stage from one world leaves it in the other.
+    a.remove_stage::<Second>().unwrap();
+    a.execute_systems().unwrap();
+    b.execute_systems().unwrap();
+
+    assert_eq!(a.get_resource_ref::<Runs>().unwrap().0, 1);
+    assert_eq!(b.get_resource_ref::<Runs>().unwrap().0, 3);
+
+    assert!(matches!(a.add_system_stage::<First>().unwrap_err().kind(), EcsError::DuplicateStage(_)));
+    assert!(matches!(a.add_schedule::<Extra>().unwrap_err().kind(), EcsError::DuplicateSchedule(_)));
+}
+
+fn exclusive(_: &mut Ecs) -> Result<()> {
+    Ok(())
+}
+
+fn nothing() {}
+
+#[test]
+fn exclusive_systems_are_ambiguous() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.add_resource(Runs::default());
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(exclusive).unwrap();
+    ecs.add_system::<Stage, _>(nothing).unwrap();
+
+    let info = ecs.schedule_info();
+    let stage = info.stages().find(|stage| stage.name.ends_with("Stage")).unwrap();
+    assert!(stage.ambiguities.is_empty());
+
+    ecs.set_ambiguity_check(AmbiguityCheck::Deny);
+    assert!(matches!(ecs.execute_startup().unwrap_err().kind(), EcsError::Ambiguous { .. }));
+
+    ecs.set_ambiguity_check(AmbiguityCheck::Ignore);
+    ecs.execute_startup().unwrap();
+
+    let info = ecs.schedule_info();
+    let stage = info.stages().find(|stage| stage.name.ends_with("Stage")).unwrap();
+    assert_eq!(stage.ambiguities.len(), 1);
+    assert_eq!(stage.conflicts.len(), 1);
+    assert!(stage.conflicts[0].exclusive);
+    assert_eq!(stage.conflicts[0].describe(), vec!["exclusive".to_string()]);
+}
+
+#[derive(Resource, Default)]
+struct Panics(bool);
+
+fn panics(mut runs: ResMut<Runs>, panics: ResRef<Panics>) {
+    runs.0 += 1;
+
+    if panics.0 {
+        panic!("the system panicked");
+    }
+}
+
+#[test]
+fn unwinding_keeps_the_schedule() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.add_resource(Runs::default());
+    ecs.add_resource(Panics(true));
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(panics).unwrap();
+    ecs.execute_startup().unwrap();
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ecs.execute_systems()));
+    assert!(result.is_err());
+
+    ecs.get_resource_mut::<Panics>().unwrap().0 = false;
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Runs>().unwrap().0, 2);
+}
+
+#[test]
+fn systems_with_the_same_name() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.add_resource(Runs::default());
+    ecs.add_system_stage::<Stage>().unwrap();
+
+    let make = || |mut runs: ResMut<Runs>| runs.0 += 1;
+
+    ecs.add_system::<Stage, _>(make()).unwrap();
+    assert!(matches!(ecs.add_system::<Stage, _>(make()).unwrap_err().kind(), EcsError::DuplicateSystem { .. }));
+    ecs.add_system::<Stage, _>(count).unwrap();
+}
+
+#[test]
+fn named_closures_from_the_same_function() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.add_resource(Runs::default());
+    ecs.add_system_stage::<Stage>().unwrap();
+
+    let make = |by: u32| move |mut runs: ResMut<Runs>| runs.0 += by;
+
+    ecs.add_system::<Stage, _>(make(1).named("one")).unwrap();
+    ecs.add_system::<Stage, _>(make(10).named("ten").after(make(1).named("one"))).unwrap();
+    assert!(matches!(ecs.add_system::<Stage, _>(make(100).named("ten")).unwrap_err().kind(), EcsError::DuplicateSystem { .. }));
+
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Runs>().unwrap().0, 11);
+
+    ecs.remove_system::<Stage, _>(make(1).named("one")).unwrap();
+    ecs.execute_systems().unwrap();
+    assert_eq!(ecs.get_resource_ref::<Runs>().unwrap().0, 21);
+}
+
+#[test]
+fn names_are_escaped_in_dot() {
+    struct Stage;
+
+    let mut ecs = Ecs::new();
+    ecs.add_system_stage::<Stage>().unwrap();
+    ecs.add_system::<Stage, _>(nothing.named("say \"hi\"\nthen leave")).unwrap();
+    ecs.execute_startup().unwrap();
+
+    let dot = ecs.schedule_info().to_dot();
+    assert!(dot.contains(r#"[label="say \"hi\"\u000athen leave"];"#));
+}